@@ -1,19 +1,26 @@
 use nix::libc;
 use std::borrow::Cow;
-use std::cell::RefMut;
 use std::io;
 use std::os::unix::prelude::CommandExt;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
 use smithay::desktop::Window;
-use smithay::utils::{Point, Serial, SERIAL_COUNTER};
+use smithay::input::pointer::{Focus, GrabStartData};
+use smithay::utils::{Serial, SERIAL_COUNTER};
 use smithay::wayland::seat::WaylandFocus;
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 
 use wzm_config::action::Direction;
+use wzm_config::ipc::Event;
 use wzm_config::keybinding::{Mode, ResizeDirection, ResizeType};
+use wzm_config::layout::Layout;
+use wzm_config::matcher::WindowMatcher;
+use wzm_config::WzmConfig;
 
-use crate::shell::{Orientation, Tree};
+use crate::grabs::MoveSurfaceGrab;
+use crate::shell::{closest_in_direction, Orientation};
+use crate::state::app_id_and_title;
 use crate::Wzm;
 
 impl Wzm {
@@ -25,96 +32,152 @@ impl Wzm {
         self.state.next_layout = Some(Orientation::Vertical)
     }
 
-    pub fn toggle_floating(&mut self) {
-        //
-    }
+    /// Re-read the configuration file, the current configuration is kept if it can't be parsed
+    pub fn reload_config(&mut self) {
+        let config = match WzmConfig::load(&self.state.config_path) {
+            Ok(config) => config,
+            Err(err) => {
+                warn!("Failed to reload config, keeping the previous one: {err:#}");
+                return;
+            }
+        };
 
-    pub fn toggle_fullscreen_window(&mut self) {
-        todo!()
-    }
+        info!("Config reloaded");
+        let gaps = config.gaps;
+        let keyboard_changed = config.keyboard != self.state.config.keyboard;
+        let input_changed = config.input != self.state.config.input;
+        let cursor_changed = config.cursor != self.state.config.cursor;
+        self.state.config = config;
 
-    pub fn move_focus(&mut self, direction: Direction) {
-        let ws = self.state.get_current_workspace();
-        let mut ws = ws.borrow_mut();
-        if let Some(window) = self.scan_window(direction, &ws) {
-            if let Some(focus) = ws.get_node_for_data(&window) {
-                ws.set_focus(focus);
-                let serial = SERIAL_COUNTER.next_serial();
-                self.toggle_window_focus(serial, &window);
+        if keyboard_changed {
+            self.apply_keyboard_config();
+        }
+
+        if input_changed {
+            self.backend
+                .configure_input_devices(&self.state.config.input);
+        }
+
+        if cursor_changed {
+            let cursor = &mut self.state.cursor;
+            cursor.reload(&self.state.config.cursor);
+            // Picked up by clients started afterwards
+            std::env::set_var("XCURSOR_THEME", cursor.theme_name());
+            std::env::set_var("XCURSOR_SIZE", cursor.size().to_string());
+        }
+
+        // The current mode might have been removed from the config
+        let config = &self.state.config;
+        self.state
+            .previous_modes
+            .retain(|mode| config.has_mode(mode));
+        if !config.has_mode(&self.state.current_mode) {
+            self.state.current_mode = Mode::Normal;
+            self.mode_changed();
+        }
+
+        for (idx, ws) in &self.state.workspaces {
+            let mut ws = ws.borrow_mut();
+            ws.set_gaps(gaps);
+            // Hidden workspaces are laid out again when switching to them
+            if !self.state.is_shown(*idx) {
+                ws.get_pending_updates();
             }
         }
+
+        self.state.emit_event(Event::ConfigReloaded);
     }
 
-    fn toggle_window_focus(&mut self, serial: Serial, window: &Window) {
+    /// Update the keymap and repeat info of the seat keyboard from the config
+    pub fn apply_keyboard_config(&mut self) {
         let keyboard = self.state.seat.get_keyboard().unwrap();
+        let config = self.state.config.keyboard.clone();
 
-        self.state.space.elements().for_each(|window| {
-            if let Some(toplevel) = window.toplevel() {
-                toplevel.send_configure();
-            }
-        });
+        if let Err(err) = keyboard.set_xkb_config(self, config.xkb_config()) {
+            warn!("Invalid keyboard config, keeping the current keymap: {err:?}");
+        }
 
-        let location = self.state.space.element_bbox(window).unwrap().loc;
+        keyboard.change_repeat_info(config.repeat_rate, config.repeat_delay);
+    }
 
-        self.state.space.map_element(window.clone(), location, true);
+    /// Switch to the next layout of the keymap, wrapping around after the last one
+    pub fn cycle_keyboard_layout(&mut self) {
+        let keyboard = self.state.seat.get_keyboard().unwrap();
+        keyboard.with_xkb_state(self, |mut context| context.cycle_next_layout());
+    }
 
-        keyboard.set_focus(self, window.wl_surface().map(Cow::into_owned), serial);
+    pub fn toggle_floating(&mut self) {
+        let ws = self.state.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+        ws.toggle_floating();
+    }
 
-        window.set_activated(true);
+    pub fn toggle_fullscreen_window(&mut self) {
+        let Some(geometry) = self.state.fullscreen_geometry() else {
+            return;
+        };
+
+        let ws = self.state.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+        ws.toggle_fullscreen_window(geometry);
+    }
+
+    pub fn toggle_fullscreen_container(&mut self) {
+        let ws = self.state.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+        ws.toggle_fullscreen_container();
+    }
 
-        if let Some(toplevel) = window.toplevel() {
-            toplevel.send_configure();
+    /// Focus the closest window in the given direction on the current workspace, or on the
+    /// workspace shown on the next output in that direction when there is none.
+    pub fn move_focus(&mut self, direction: Direction) {
+        let ws = self.state.get_current_workspace();
+        let moved = ws.borrow_mut().focus_in_direction(direction);
+        if !moved && !self.focus_output_in_direction(direction) {
+            return;
         }
+
+        let window = self.state.get_current_workspace().borrow().get_focus();
+        self.set_keyboard_focus(window.as_ref());
     }
 
-    fn scan_window(
-        &mut self,
-        direction: Direction,
-        ws: &RefMut<'_, Tree<Window>>,
-    ) -> Option<Window> {
-        let mut window = None;
-        if let Some(focus) = ws.get_focus() {
-            let loc = self
-                .state
-                .space
-                .element_location(&focus)
-                .expect("window should have a location");
-
-            let (mut x, mut y) = (loc.x, loc.y);
-            let width = focus.geometry().size.w;
-            let height = focus.geometry().size.h;
-
-            // Move one pixel inside the window to avoid being out of bbox after converting to f64
-            match direction {
-                Direction::Right => {
-                    x += width;
-                    y += 1;
-                }
-                Direction::Down => y += height - 1,
-                Direction::Left => y += 1,
-                Direction::Up => x += 1,
-            }
+    /// Make the workspace shown on the closest output in the given direction the current one,
+    /// focusing its window closest to the previously focused one.
+    /// Returns false if there is no output in that direction.
+    fn focus_output_in_direction(&mut self, direction: Direction) -> bool {
+        let Some(origin) = self
+            .state
+            .focused_output()
+            .and_then(|output| self.state.space.output_geometry(&output))
+        else {
+            return false;
+        };
 
-            let mut point = Point::from((x, y)).to_f64();
-            while window.is_none() {
-                if self.state.space.output_under(point).next().is_none() {
-                    break;
-                }
+        let outputs = self
+            .state
+            .shown_workspaces
+            .iter()
+            .filter(|(_, num)| *num != self.state.current_workspace)
+            .filter_map(|(output, num)| Some((*num, self.state.space.output_geometry(output)?)));
 
-                direction.advance_point(&mut point);
+        let Some(num) = closest_in_direction(direction, &origin, outputs) else {
+            return false;
+        };
 
-                window = {
-                    self.state
-                        .space
-                        .element_under(point)
-                        .map(|(window, _)| window.clone())
-                };
-            }
-        }
-        window
+        let focused = self.state.get_current_workspace().borrow().get_focus();
+        let origin = focused
+            .and_then(|window| self.state.space.element_geometry(&window))
+            .unwrap_or(origin);
+
+        self.state.current_workspace = num;
+        let ws = self.state.get_current_workspace();
+        ws.borrow_mut().focus_closest(direction, &origin);
+        self.state.emit_event(Event::WorkspaceSwitched(num));
+        true
     }
 
     pub fn close(&mut self) {
+        let closed = self.state.focused_window_info();
         let tree = self.state.get_current_workspace();
         let mut tree = tree.borrow_mut();
         if let Some(toplevel) = tree.get_focus().as_ref().and_then(|w| w.toplevel()) {
@@ -122,8 +185,14 @@ impl Wzm {
         };
 
         tree.remove();
+        let focus = tree.get_focus();
+        drop(tree);
 
-        if let Some(window) = tree.get_focus() {
+        if let Some(closed) = closed {
+            self.state.emit_event(Event::WindowClosed(closed));
+        }
+
+        if let Some(window) = focus {
             let handle = self
                 .state
                 .seat
@@ -135,59 +204,168 @@ impl Wzm {
         }
     }
 
+    pub fn move_container(&mut self, direction: Direction) {
+        let ws = self.state.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+        ws.move_container(direction);
+    }
+
     pub fn move_window(&mut self, direction: Direction) {
         let tree = self.state.get_current_workspace();
         let mut tree = tree.borrow_mut();
-        if let Some(window) = self.scan_window(direction, &tree) {
-            if let Some((tree_id, leaf_id)) = tree.get_node_for_data(&window) {
-                tree.move_node(tree_id, leaf_id);
+        if let Some((tree_id, leaf_id)) = tree.leaf_in_direction(direction) {
+            tree.move_node(tree_id, leaf_id);
+        }
+    }
+
+    /// Switch to the workspace with the given index, creating it on the focused output if it
+    /// does not exist yet. It replaces the workspace shown on its output, which is dropped
+    /// if empty.
+    pub fn switch_workspace(&mut self, num: u8) {
+        if num == self.state.current_workspace {
+            return;
+        }
+
+        let ws = self.state.get_or_create_workspace(num);
+        self.state.current_workspace = num;
+        if let Some(previous) = self.state.show_workspace(num) {
+            if self.state.workspaces[&previous].borrow().is_empty() {
+                self.state.workspaces.remove(&previous);
+                self.state.workspace_outputs.remove(&previous);
             }
         }
+
+        let focus = ws.borrow().get_focus();
+        self.set_keyboard_focus(focus.as_ref());
+        self.state.emit_event(Event::WorkspaceSwitched(num));
     }
 
-    pub fn move_request_server(&mut self, serial: Serial, button_used: u32) {
-        /*        debug!("Initiating move request from server");
+    /// Send the focused window to the workspace with the given index without switching to it.
+    pub fn move_window_to_workspace(&mut self, num: u8) {
+        if num == self.state.current_workspace {
+            return;
+        }
 
-                let pointer = self.seat.get_pointer().expect("seat had no pointer");
-                let point = pointer.current_location();
-                let Some((window, window_loc)) = self.space.element_under(point) else {
-                    debug!("no window below cursor");
-                    return;
-                };
+        let ws = self.state.get_current_workspace();
+        let target = self.state.get_or_create_workspace(num);
+        let mut ws = ws.borrow_mut();
+        let mut target = target.borrow_mut();
+        let Some(window) = ws.move_focused_to(&mut target) else {
+            return;
+        };
 
-                // Return early if this is not a toplevel window
-                if window.user_data().get::<WindowState>().is_none() {
-                    debug!("not a toplevel window");
-                    return;
-                }
+        // Geometries of a hidden workspace are applied when switching to it
+        let shown = self.state.is_shown(num);
+        if !shown {
+            target.get_pending_updates();
+        }
 
-                let start_data = smithay::input::pointer::GrabStartData {
-                    focus: pointer.current_focus().map(|focus| (focus, window_loc)),
-                    button: button_used,
-                    location: pointer.current_location(),
-                };
+        drop(target);
+        let focus = ws.get_focus();
+        drop(ws);
 
-                let grab = MoveSurfaceGrab {
-                    start_data,
-                    window: window.clone(),
-                    initial_window_location: window_loc,
-                };
+        if !shown {
+            self.state.space.unmap_elem(&window);
+        }
 
-                pointer.set_grab(self, grab, serial, Focus::Clear);
-        */
+        self.set_keyboard_focus(focus.as_ref());
     }
 
-    pub fn toggle_resize(&mut self) {
-        self.state.current_mode = match self.state.current_mode {
-            Mode::Normal => Mode::Resize,
-            Mode::Resize => Mode::Normal,
+    pub fn set_keyboard_focus(&mut self, window: Option<&Window>) {
+        let keyboard = self
+            .state
+            .seat
+            .get_keyboard()
+            .expect("Should have a keyboard seat");
+
+        let serial = SERIAL_COUNTER.next_serial();
+        let surface = window.and_then(|w| w.wl_surface().map(Cow::into_owned));
+        keyboard.set_focus(self, surface, serial);
+    }
+
+    /// Start moving the floating window under the pointer
+    pub fn move_request_server(&mut self, serial: Serial, button_used: u32) {
+        debug!("Initiating move request from server");
+
+        let pointer = self.state.seat.get_pointer().expect("seat had no pointer");
+        let point = pointer.current_location();
+        let Some((window, window_loc)) = self
+            .state
+            .space
+            .element_under(point)
+            .map(|(window, loc)| (window.clone(), loc))
+        else {
+            debug!("no window below cursor");
+            return;
+        };
+
+        if !self.state.is_floating(&window) {
+            debug!("not a floating window");
+            return;
+        }
+
+        let start_data = GrabStartData {
+            focus: pointer.current_focus().map(|focus| (focus, window_loc)),
+            button: button_used,
+            location: pointer.current_location(),
+        };
+
+        let grab = MoveSurfaceGrab {
+            start_data,
+            window,
+            initial_window_location: window_loc,
         };
+
+        pointer.set_grab(self, grab, serial, Focus::Clear);
+    }
+
+    pub fn toggle_resize(&mut self) {
+        if self.state.resize_mode() {
+            self.exit_mode();
+        } else {
+            self.enter_mode(Mode::Resize);
+        }
+    }
+
+    /// Switch to the given binding mode, remembering the current one for `ExitMode`
+    pub fn enter_mode(&mut self, mode: Mode) {
+        if !self.state.config.has_mode(&mode) {
+            warn!("unknown binding mode '{mode}'");
+            return;
+        }
+
+        if mode == self.state.current_mode {
+            return;
+        }
+
+        let previous = std::mem::replace(&mut self.state.current_mode, mode);
+        self.state.previous_modes.push(previous);
+        self.mode_changed();
+    }
+
+    /// Return to the mode active before the last `enter_mode`, or to `Normal`
+    pub fn exit_mode(&mut self) {
+        let previous = self.state.previous_modes.pop().unwrap_or_default();
+        if previous == self.state.current_mode {
+            return;
+        }
+
+        self.state.current_mode = previous;
+        self.mode_changed();
+    }
+
+    /// Publish the current binding mode to IPC subscribers
+    pub fn mode_changed(&mut self) {
+        let mode = self.state.current_mode.to_string();
+        info!("Entering {mode} mode");
+        self.state.emit_event(Event::ModeChanged(mode));
     }
 
     pub fn toggle_layout(&mut self) {
         let ws = self.state.get_current_workspace();
-        let mut ws = ws.borrow_mut();
-        ws.toggle_layout();
+        let orientation = ws.borrow_mut().toggle_layout();
+        self.state
+            .emit_event(Event::LayoutChanged(orientation.into()));
     }
 
     pub fn resize(&mut self, kind: ResizeType, direction: ResizeDirection, amount: u32) {
@@ -195,6 +373,55 @@ impl Wzm {
         let mut ws = ws.borrow_mut();
         ws.resize(kind, direction, amount as i32);
     }
+
+    /// Write the tiled layout of the current workspace, windows are saved with a matcher
+    /// on their exact app_id, or on their title for windows without one
+    pub fn save_layout(&mut self, path: PathBuf) {
+        let path = self.layout_path(path);
+        let layout = self
+            .state
+            .get_current_workspace()
+            .borrow()
+            .save_layout(|window| {
+                let (app_id, title) = app_id_and_title(window);
+                WindowMatcher::exact(app_id.as_deref(), title.as_deref())
+            });
+
+        match layout.save(&path) {
+            Ok(()) => info!("Layout saved to {}", path.display()),
+            Err(err) => warn!("Failed to save layout: {err:#}"),
+        }
+    }
+
+    /// Append a saved layout to the current workspace, its placeholders are filled
+    /// by the next matching windows
+    pub fn load_layout(&mut self, path: PathBuf) {
+        let path = self.layout_path(path);
+        let layout = match Layout::load(&path) {
+            Ok(layout) => layout,
+            Err(err) => {
+                warn!("Failed to load layout: {err:#}");
+                return;
+            }
+        };
+
+        info!(
+            "Loaded layout {} with {} placeholders",
+            path.display(),
+            layout.windows()
+        );
+
+        let ws = self.state.get_current_workspace();
+        ws.borrow_mut().load_layout(layout);
+    }
+
+    // Relative layout paths are resolved from the config directory
+    fn layout_path(&self, path: PathBuf) -> PathBuf {
+        match self.state.config_path.parent() {
+            Some(dir) => dir.join(path),
+            None => path,
+        }
+    }
 }
 
 /// Spawns the command to run independently of the compositor.
@@ -1,17 +1,26 @@
 use crate::backend::udev::Udev;
 use crate::backend::winit::Winit;
+use crate::cursor::PointerRenderElement;
 use crate::State;
 use smithay::backend::allocator::dmabuf::Dmabuf;
 use smithay::backend::renderer::gles::GlesRenderer;
-use smithay::backend::renderer::ImportEgl;
+use smithay::backend::renderer::{ImportAll, ImportDma, ImportEgl, ImportMem};
+use smithay::desktop::space::SpaceRenderElements;
 use smithay::output::Output;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::utils::Transform;
-use tracing::warn;
+use tracing::{debug, warn};
+use wzm_config::input::InputConfig;
 
 pub mod udev;
 pub mod winit;
 
+smithay::render_elements! {
+    pub OutputRenderElements<R, E> where R: ImportAll + ImportMem;
+    Space=SpaceRenderElements<R, E>,
+    Pointer=PointerRenderElement<R>,
+}
+
 // Thank you niri
 #[derive(PartialEq, Eq)]
 pub enum RenderResult {
@@ -41,46 +50,71 @@ impl Backend {
                 output.change_current_state(None, Some(Transform::Flipped180), None, None);
                 wzm.space.map_output(output, (0, 0));
             }
-            Backend::Udev(_) => todo!(),
+            Backend::Udev(udev) => udev.init(wzm),
         }
     }
 
     pub fn seat_name(&self) -> String {
-        todo!()
+        match self {
+            Backend::Winit(_) => "winit".to_string(),
+            Backend::Udev(udev) => udev.seat_name(),
+        }
     }
 
     pub fn with_primary_renderer<T>(
         &mut self,
-        _f: impl FnOnce(&mut GlesRenderer) -> T,
+        f: impl FnOnce(&mut GlesRenderer) -> T,
     ) -> Option<T> {
-        todo!()
+        match self {
+            Backend::Winit(winit) => Some(f(winit.renderer())),
+            Backend::Udev(udev) => udev.renderer().map(|mut renderer| f(renderer.as_mut())),
+        }
     }
 
     pub fn render(&mut self, wzm: &mut State) {
         match self {
             Backend::Winit(winit) => winit.render(wzm),
-            Backend::Udev(_) => todo!(),
+            Backend::Udev(udev) => udev.render(wzm),
         };
     }
 
-    pub fn change_vt(&mut self, _vt: i32) {
-        todo!()
+    pub fn change_vt(&mut self, vt: i32) {
+        match self {
+            Backend::Winit(_) => debug!("vt switching is not supported with winit"),
+            Backend::Udev(udev) => udev.change_vt(vt),
+        }
+    }
+
+    pub fn configure_input_devices(&mut self, config: &[InputConfig]) {
+        match self {
+            Backend::Winit(_) => {}
+            Backend::Udev(udev) => udev.configure_input_devices(config),
+        }
     }
 
     pub fn suspend(&mut self) {
-        todo!()
+        match self {
+            Backend::Winit(_) => {}
+            Backend::Udev(udev) => udev.suspend(),
+        }
     }
 
-    pub fn import_dmabuf(&mut self, _dmabuf: &Dmabuf) -> bool {
-        todo!()
+    pub fn import_dmabuf(&mut self, dmabuf: &Dmabuf) -> bool {
+        match self {
+            Backend::Winit(winit) => winit.renderer().import_dmabuf(dmabuf, None).is_ok(),
+            Backend::Udev(udev) => udev.import_dmabuf(dmabuf),
+        }
     }
 
     pub fn early_import(&mut self, _surface: &WlSurface) {
         todo!()
     }
 
-    pub fn set_monitors_active(&mut self, _active: bool) {
-        todo!()
+    pub fn set_monitors_active(&mut self, active: bool) {
+        match self {
+            Backend::Winit(_) => {}
+            Backend::Udev(udev) => udev.set_monitors_active(active),
+        }
     }
 
     pub fn on_output_config_changed(&mut self, _wzm: &mut State) {
@@ -90,9 +124,7 @@ impl Backend {
     pub fn get_output(&self) -> &Output {
         match self {
             Backend::Winit(winit) => winit.output(),
-            Backend::Udev(_) => {
-                todo!()
-            }
+            Backend::Udev(udev) => udev.output().expect("udev backend should have an output"),
         }
     }
 }
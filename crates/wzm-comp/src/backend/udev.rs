@@ -1,31 +1,70 @@
-use crate::DisplayHandle;
-use smithay::backend::allocator::gbm::GbmDevice;
-use smithay::backend::drm::{DrmDevice, DrmDeviceFd, DrmNode};
-use smithay::backend::renderer::gles::GlesRenderer;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use smithay::backend::allocator::gbm::{GbmAllocator, GbmBufferFlags, GbmDevice};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::drm::compositor::DrmCompositor;
+use smithay::backend::drm::{
+    DrmDevice, DrmDeviceFd, DrmEvent, DrmEventMetadata, DrmNode, NodeType,
+};
+use smithay::backend::egl::{EGLDevice, EGLDisplay};
+use smithay::backend::input::InputEvent;
+use smithay::backend::libinput::{LibinputInputBackend, LibinputSessionInterface};
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
 use smithay::backend::renderer::multigpu::gbm::GbmGlesBackend;
 use smithay::backend::renderer::multigpu::{GpuManager, MultiRenderer};
-use smithay::backend::renderer::DebugFlags;
+use smithay::backend::renderer::{DebugFlags, ImportDma, ImportEgl};
 use smithay::backend::session::libseat::LibSeatSession;
-use smithay::reexports::calloop::RegistrationToken;
-use smithay::reexports::drm::control::{connector, crtc};
-use smithay::wayland::compositor::SurfaceData;
-use smithay::wayland::dmabuf::{DmabufGlobal, DmabufState};
+use smithay::backend::session::{Event as SessionEvent, Session};
+use smithay::backend::udev::{primary_gpu, UdevBackend, UdevEvent};
+use smithay::output::{Mode as WlMode, Output, PhysicalProperties, Subpixel};
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
+use smithay::reexports::calloop::{LoopHandle, RegistrationToken};
+use smithay::reexports::drm::control::{connector, crtc, Device as ControlDevice, ModeTypeFlags};
+use smithay::reexports::input::{
+    AccelProfile as LibinputAccelProfile, Device as InputDevice, DeviceCapability, Libinput,
+    ScrollMethod as LibinputScrollMethod,
+};
+use smithay::reexports::rustix::fs::OFlags;
+use smithay::reexports::wayland_server::backend::GlobalId;
+use smithay::utils::{DeviceFd, Logical, Point, Rectangle};
+use smithay::wayland::dmabuf::{DmabufFeedbackBuilder, DmabufGlobal, DmabufState};
 use smithay::wayland::drm_lease::{DrmLease, DrmLeaseState};
-use smithay_drm_extras::drm_scanner::DrmScanner;
-use std::collections::HashMap;
+use smithay_drm_extras::drm_scanner::{DrmScanEvent, DrmScanner};
+use smithay_drm_extras::edid::EdidInfo;
+use tracing::{debug, error, info, warn};
+
+use wzm_config::input::{self, AccelProfile, InputConfig, ScrollMethod};
+
+use crate::backend::{Backend, OutputRenderElements};
+use crate::{DisplayHandle, State, Wzm};
+
+// Color formats supported by the DrmCompositor, in order of preference
+const SUPPORTED_FORMATS: &[Fourcc] = &[
+    Fourcc::Abgr2101010,
+    Fourcc::Argb2101010,
+    Fourcc::Abgr8888,
+    Fourcc::Argb8888,
+];
+
+type GbmDrmCompositor =
+    DrmCompositor<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>;
 
 pub struct Udev {
     pub session: LibSeatSession,
     dh: DisplayHandle,
+    loop_handle: LoopHandle<'static, Wzm>,
     pub(crate) dmabuf_state: Option<(DmabufState, DmabufGlobal)>,
     pub(crate) primary_gpu: DrmNode,
     pub(crate) gpus: GpuManager<GbmGlesBackend<GlesRenderer, DrmDeviceFd>>,
     pub(crate) backends: HashMap<DrmNode, BackendData>,
-    // pointer_images: Vec<(xcursor::parser::Image, MemoryRenderBuffer)>,
-    // pointer_element: PointerElement,
-    // TODO: pointer_image: crate::cursor::Cursor,
     debug_flags: DebugFlags,
-    keyboards: Vec<smithay::reexports::input::Device>,
+    libinput: Libinput,
+    monitors_active: bool,
+    udev_backend: Option<UdevBackend>,
+    pointers: Vec<InputDevice>,
 }
 
 pub struct BackendData {
@@ -40,6 +79,17 @@ pub struct BackendData {
     registration_token: RegistrationToken,
 }
 
+/// A DRM surface driving a single output
+struct SurfaceData {
+    output: Output,
+    global: GlobalId,
+    compositor: GbmDrmCompositor,
+    /// A frame was queued and we are waiting for the next VBlank
+    frame_pending: bool,
+    /// Render again once this timer fires, the last frame had no damage
+    render_timer: Option<RegistrationToken>,
+}
+
 type UdevRenderer<'a> = MultiRenderer<
     'a,
     'a,
@@ -47,8 +97,979 @@ type UdevRenderer<'a> = MultiRenderer<
     GbmGlesBackend<GlesRenderer, DrmDeviceFd>,
 >;
 
+/// A connector state change reported by the [`DrmScanner`], with the crtc it was given
+#[derive(Debug)]
+enum ConnectorEvent<C> {
+    Connected(C, Option<crtc::Handle>),
+    Disconnected(C, Option<crtc::Handle>),
+}
+
+/// An output to create or destroy after a connector scan
+#[derive(Debug, PartialEq)]
+enum OutputChange<C> {
+    Add { connector: C, crtc: crtc::Handle },
+    Remove { connector: C, crtc: crtc::Handle },
+}
+
 #[derive(Debug, PartialEq)]
 struct UdevOutputId {
     device_id: DrmNode,
     crtc: crtc::Handle,
 }
+
+impl Udev {
+    /// Open a libseat session and register the udev and libinput event sources.
+    /// DRM devices are only added to the compositor once [`Udev::init`] is called.
+    pub fn new(loop_handle: LoopHandle<'static, Wzm>, dh: DisplayHandle) -> anyhow::Result<Self> {
+        let (session, notifier) = LibSeatSession::new()?;
+        let seat_name = session.seat();
+
+        let primary_gpu = primary_gpu(&seat_name)?
+            .and_then(|path| DrmNode::from_path(path).ok())
+            .and_then(|node| node.node_with_type(NodeType::Render)?.ok())
+            .or_else(|| {
+                smithay::backend::udev::all_gpus(&seat_name)
+                    .ok()?
+                    .into_iter()
+                    .find_map(|path| DrmNode::from_path(path).ok())
+            })
+            .ok_or_else(|| anyhow::anyhow!("no GPU found on seat {seat_name}"))?;
+
+        info!("Using {primary_gpu} as primary gpu");
+
+        let gpus = GpuManager::new(GbmGlesBackend::default())?;
+
+        let udev_backend = UdevBackend::new(&seat_name)?;
+
+        let mut libinput = Libinput::new_with_udev::<LibinputSessionInterface<LibSeatSession>>(
+            session.clone().into(),
+        );
+        libinput
+            .udev_assign_seat(&seat_name)
+            .map_err(|_| anyhow::anyhow!("failed to assign seat {seat_name} to libinput"))?;
+
+        let libinput_backend = LibinputInputBackend::new(libinput.clone());
+
+        loop_handle
+            .insert_source(libinput_backend, |event, _, data| {
+                if let Backend::Udev(udev) = &mut data.backend {
+                    udev.on_input_device_event(&event, &data.state.config.input);
+                }
+
+                data.process_input_event(event)
+            })
+            .map_err(|err| anyhow::anyhow!("failed to insert libinput source: {err}"))?;
+
+        loop_handle
+            .insert_source(notifier, |event, _, data| {
+                let Backend::Udev(udev) = &mut data.backend else {
+                    return;
+                };
+
+                match event {
+                    SessionEvent::PauseSession => udev.suspend(),
+                    SessionEvent::ActivateSession => udev.resume(&mut data.state),
+                }
+            })
+            .map_err(|err| anyhow::anyhow!("failed to insert session source: {err}"))?;
+
+        Ok(Self {
+            session,
+            dh,
+            loop_handle,
+            dmabuf_state: None,
+            primary_gpu,
+            gpus,
+            backends: HashMap::new(),
+            debug_flags: DebugFlags::empty(),
+            libinput,
+            monitors_active: true,
+            udev_backend: Some(udev_backend),
+            pointers: vec![],
+        })
+    }
+
+    /// Add all the devices currently known to udev, start listening for hotplug events
+    /// and initialize dmabuf support on the primary gpu.
+    pub fn init(&mut self, state: &mut State) {
+        let Some(udev_backend) = self.udev_backend.take() else {
+            return;
+        };
+
+        let devices: Vec<_> = udev_backend
+            .device_list()
+            .map(|(device_id, path)| (device_id, path.to_path_buf()))
+            .collect();
+
+        for (device_id, path) in devices {
+            match DrmNode::from_dev_id(device_id) {
+                Ok(node) => self.device_added(node, &path, state),
+                Err(err) => warn!("failed to get drm node for {path:?}: {err}"),
+            }
+        }
+
+        self.init_dmabuf_state();
+
+        self.loop_handle
+            .insert_source(udev_backend, |event, _, data| {
+                let Backend::Udev(udev) = &mut data.backend else {
+                    return;
+                };
+
+                match event {
+                    UdevEvent::Added { device_id, path } => match DrmNode::from_dev_id(device_id) {
+                        Ok(node) => udev.device_added(node, &path, &mut data.state),
+                        Err(err) => warn!("failed to get drm node for {path:?}: {err}"),
+                    },
+                    UdevEvent::Changed { device_id } => {
+                        if let Ok(node) = DrmNode::from_dev_id(device_id) {
+                            udev.device_changed(node, &mut data.state)
+                        }
+                    }
+                    UdevEvent::Removed { device_id } => {
+                        if let Ok(node) = DrmNode::from_dev_id(device_id) {
+                            udev.device_removed(node, &mut data.state)
+                        }
+                    }
+                }
+            })
+            .expect("Failed to init the udev event source.");
+    }
+
+    fn init_dmabuf_state(&mut self) {
+        let mut renderer = match self.gpus.single_renderer(&self.primary_gpu) {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                warn!("failed to get primary renderer, dmabuf is disabled: {err}");
+                return;
+            }
+        };
+
+        if let Err(err) = renderer.bind_wl_display(&self.dh) {
+            warn!("error binding renderer wl_display: {err}");
+        }
+
+        let formats = renderer.dmabuf_formats().collect::<Vec<_>>();
+        drop(renderer);
+
+        let default_feedback = DmabufFeedbackBuilder::new(self.primary_gpu.dev_id(), formats)
+            .build()
+            .expect("failed to build dmabuf feedback");
+
+        let mut dmabuf_state = DmabufState::new();
+        let dmabuf_global =
+            dmabuf_state.create_global_with_default_feedback::<Wzm>(&self.dh, &default_feedback);
+        self.dmabuf_state = Some((dmabuf_state, dmabuf_global));
+    }
+
+    fn on_input_device_event(
+        &mut self,
+        event: &InputEvent<LibinputInputBackend>,
+        config: &[InputConfig],
+    ) {
+        match event {
+            InputEvent::DeviceAdded { device } => {
+                if device.has_capability(DeviceCapability::Pointer) {
+                    let mut device = device.clone();
+                    configure_pointer(&mut device, config);
+                    self.pointers.push(device);
+                }
+            }
+            InputEvent::DeviceRemoved { device } => {
+                self.pointers.retain(|pointer| pointer != device);
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply the input config to the connected pointer devices
+    pub fn configure_input_devices(&mut self, config: &[InputConfig]) {
+        for device in &mut self.pointers {
+            configure_pointer(device, config);
+        }
+    }
+
+    fn device_added(&mut self, node: DrmNode, path: &Path, state: &mut State) {
+        if let Err(err) = self.try_add_device(node, path) {
+            warn!("failed to add drm device {path:?}: {err}");
+            return;
+        }
+
+        self.device_changed(node, state);
+    }
+
+    fn try_add_device(&mut self, node: DrmNode, path: &Path) -> anyhow::Result<()> {
+        let fd = self.session.open(
+            path,
+            OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK,
+        )?;
+
+        let fd = DrmDeviceFd::new(DeviceFd::from(fd));
+        let (drm, notifier) = DrmDevice::new(fd.clone(), true)?;
+        let gbm = GbmDevice::new(fd)?;
+
+        let registration_token = self
+            .loop_handle
+            .insert_source(notifier, move |event, metadata, data| match event {
+                DrmEvent::VBlank(crtc) => {
+                    if let Backend::Udev(udev) = &mut data.backend {
+                        udev.frame_finish(node, crtc, metadata, &mut data.state);
+                    }
+                }
+                DrmEvent::Error(err) => error!("drm error on {node}: {err}"),
+            })
+            .map_err(|err| anyhow::anyhow!("failed to insert drm source: {err}"))?;
+
+        let render_node = {
+            let display = unsafe { EGLDisplay::new(gbm.clone())? };
+            EGLDevice::device_for_display(&display)
+                .ok()
+                .and_then(|device| device.try_get_render_node().ok().flatten())
+                .unwrap_or(node)
+        };
+
+        self.gpus.as_mut().add_node(render_node, gbm.clone())?;
+
+        let leasing_global = DrmLeaseState::new::<Wzm>(&self.dh, &node)
+            .map_err(|err| warn!("failed to initialize drm lease global for {node}: {err}"))
+            .ok();
+
+        self.backends.insert(
+            node,
+            BackendData {
+                surfaces: HashMap::new(),
+                non_desktop_connectors: vec![],
+                leasing_global,
+                active_leases: vec![],
+                gbm,
+                drm,
+                drm_scanner: DrmScanner::new(),
+                render_node,
+                registration_token,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Rescan the connectors of a device and create or destroy outputs accordingly
+    fn device_changed(&mut self, node: DrmNode, state: &mut State) {
+        let Some(device) = self.backends.get_mut(&node) else {
+            return;
+        };
+
+        let events: Vec<_> = device
+            .drm_scanner
+            .scan_connectors(&device.drm)
+            .into_iter()
+            .map(|event| match event {
+                DrmScanEvent::Connected { connector, crtc } => {
+                    ConnectorEvent::Connected(connector, crtc)
+                }
+                DrmScanEvent::Disconnected { connector, crtc } => {
+                    ConnectorEvent::Disconnected(connector, crtc)
+                }
+            })
+            .collect();
+
+        let active: Vec<_> = device.surfaces.keys().copied().collect();
+        for change in output_changes(events, &active) {
+            match change {
+                OutputChange::Add { connector, crtc } => {
+                    self.connector_connected(node, connector, crtc, state)
+                }
+                OutputChange::Remove { connector, crtc } => {
+                    self.connector_disconnected(node, connector, crtc, state)
+                }
+            }
+        }
+    }
+
+    fn device_removed(&mut self, node: DrmNode, state: &mut State) {
+        let Some(device) = self.backends.get(&node) else {
+            return;
+        };
+
+        let crtcs: Vec<_> = device
+            .drm_scanner
+            .crtcs()
+            .map(|(info, crtc)| (info.clone(), crtc))
+            .collect();
+
+        for (info, crtc) in crtcs {
+            self.connector_disconnected(node, info, crtc, state);
+        }
+
+        if let Some(mut device) = self.backends.remove(&node) {
+            if let Some(mut leasing_global) = device.leasing_global.take() {
+                leasing_global.disable_global::<Wzm>();
+            }
+
+            self.gpus.as_mut().remove_node(&device.render_node);
+            self.loop_handle.remove(device.registration_token);
+        }
+
+        debug!("removed drm device {node}");
+    }
+
+    fn connector_connected(
+        &mut self,
+        node: DrmNode,
+        connector: connector::Info,
+        crtc: crtc::Handle,
+        state: &mut State,
+    ) {
+        let Some(device) = self.backends.get_mut(&node) else {
+            return;
+        };
+
+        let output_name = connector_name(connector.interface(), connector.interface_id());
+        info!(?crtc, "connector connected: {output_name}");
+
+        let non_desktop = device
+            .drm
+            .get_properties(connector.handle())
+            .ok()
+            .and_then(|props| {
+                let (handles, values) = props.as_props_and_values();
+                handles
+                    .iter()
+                    .zip(values.iter())
+                    .find_map(|(handle, value)| {
+                        let info = device.drm.get_property(*handle).ok()?;
+                        (info.name().to_str().ok()? == "non-desktop").then_some(*value != 0)
+                    })
+            })
+            .unwrap_or(false);
+
+        if non_desktop {
+            info!("{output_name} is a non-desktop connector, it will be available for leasing");
+            device
+                .non_desktop_connectors
+                .push((connector.handle(), crtc));
+
+            if let Some(leasing_global) = device.leasing_global.as_mut() {
+                leasing_global.add_connector::<Wzm>(connector.handle(), output_name, String::new());
+            }
+
+            return;
+        }
+
+        let Some(drm_mode) = connector
+            .modes()
+            .iter()
+            .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+            .or_else(|| connector.modes().first())
+            .copied()
+        else {
+            warn!("no mode available for {output_name}");
+            return;
+        };
+
+        let surface = match device
+            .drm
+            .create_surface(crtc, drm_mode, &[connector.handle()])
+        {
+            Ok(surface) => surface,
+            Err(err) => {
+                warn!("failed to create drm surface for {output_name}: {err}");
+                return;
+            }
+        };
+
+        let (make, model) = EdidInfo::for_connector(&device.drm, connector.handle())
+            .map(|info| (info.manufacturer, info.model))
+            .unwrap_or_else(|| ("Unknown".into(), "Unknown".into()));
+
+        let (physical_width, physical_height) = connector.size().unwrap_or((0, 0));
+
+        let output = Output::new(
+            output_name.clone(),
+            PhysicalProperties {
+                size: (physical_width as i32, physical_height as i32).into(),
+                subpixel: Subpixel::Unknown,
+                make,
+                model,
+            },
+        );
+
+        let global = output.create_global::<Wzm>(&self.dh);
+        let mode = WlMode::from(drm_mode);
+        output.change_current_state(Some(mode), None, None, None);
+        output.set_preferred(mode);
+        output.user_data().insert_if_missing(|| UdevOutputId {
+            device_id: node,
+            crtc,
+        });
+
+        let location = next_output_location(
+            state
+                .space
+                .outputs()
+                .filter_map(|output| state.space.output_geometry(output)),
+        );
+        state.space.map_output(&output, location);
+
+        let allocator = GbmAllocator::new(
+            device.gbm.clone(),
+            GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
+        );
+
+        let render_formats = match self.gpus.single_renderer(&device.render_node) {
+            Ok(mut renderer) => renderer
+                .as_mut()
+                .egl_context()
+                .dmabuf_render_formats()
+                .clone(),
+            Err(err) => {
+                warn!("failed to get renderer for {output_name}: {err}");
+                return;
+            }
+        };
+
+        let compositor = match DrmCompositor::new(
+            &output,
+            surface,
+            None,
+            allocator,
+            device.gbm.clone(),
+            SUPPORTED_FORMATS,
+            render_formats,
+            device.drm.cursor_size(),
+            Some(device.gbm.clone()),
+        ) {
+            Ok(compositor) => compositor,
+            Err(err) => {
+                warn!("failed to create drm compositor for {output_name}: {err}");
+                state.space.unmap_output(&output);
+                self.dh.remove_global::<Wzm>(global);
+                return;
+            }
+        };
+
+        state.add_output_workspace(&output);
+        device.surfaces.insert(
+            crtc,
+            SurfaceData {
+                output,
+                global,
+                compositor,
+                frame_pending: false,
+                render_timer: None,
+            },
+        );
+
+        self.render_surface(node, crtc, state);
+    }
+
+    fn connector_disconnected(
+        &mut self,
+        node: DrmNode,
+        connector: connector::Info,
+        crtc: crtc::Handle,
+        state: &mut State,
+    ) {
+        let Some(device) = self.backends.get_mut(&node) else {
+            return;
+        };
+
+        if let Some(idx) = device
+            .non_desktop_connectors
+            .iter()
+            .position(|(handle, _)| *handle == connector.handle())
+        {
+            device.non_desktop_connectors.remove(idx);
+            if let Some(leasing_global) = device.leasing_global.as_mut() {
+                leasing_global.withdraw_connector(connector.handle());
+            }
+
+            return;
+        }
+
+        if let Some(surface) = device.surfaces.remove(&crtc) {
+            info!(?crtc, "connector disconnected: {}", surface.output.name());
+            if let Some(token) = surface.render_timer {
+                self.loop_handle.remove(token);
+            }
+
+            state.space.unmap_output(&surface.output);
+            state.remove_output(&surface.output);
+            self.dh.remove_global::<Wzm>(surface.global);
+        }
+    }
+
+    /// The queued frame is on screen, render the next one
+    fn frame_finish(
+        &mut self,
+        node: DrmNode,
+        crtc: crtc::Handle,
+        _metadata: &mut Option<DrmEventMetadata>,
+        state: &mut State,
+    ) {
+        let Some(surface) = self
+            .backends
+            .get_mut(&node)
+            .and_then(|device| device.surfaces.get_mut(&crtc))
+        else {
+            return;
+        };
+
+        if let Err(err) = surface.compositor.frame_submitted() {
+            warn!("error while marking frame as submitted: {err}");
+        }
+
+        surface.frame_pending = false;
+        self.render_surface(node, crtc, state);
+    }
+
+    /// Render the surface after the delay, unless a render is already scheduled
+    fn schedule_render(&mut self, node: DrmNode, crtc: crtc::Handle, delay: Duration) {
+        let Some(surface) = self
+            .backends
+            .get_mut(&node)
+            .and_then(|device| device.surfaces.get_mut(&crtc))
+        else {
+            return;
+        };
+
+        if surface.render_timer.is_some() {
+            return;
+        }
+
+        let timer = Timer::from_duration(delay);
+        let token = self
+            .loop_handle
+            .insert_source(timer, move |_, _, data| {
+                if let Backend::Udev(udev) = &mut data.backend {
+                    if let Some(surface) = udev
+                        .backends
+                        .get_mut(&node)
+                        .and_then(|device| device.surfaces.get_mut(&crtc))
+                    {
+                        surface.render_timer = None;
+                    }
+
+                    udev.render_surface(node, crtc, &mut data.state);
+                }
+
+                TimeoutAction::Drop
+            })
+            .map_err(|err| warn!("failed to schedule render: {err}"))
+            .ok();
+
+        surface.render_timer = token;
+    }
+
+    /// Pause input and DRM devices, called when the session is deactivated (e.g. on VT switch)
+    pub fn suspend(&mut self) {
+        info!("pausing session");
+        self.libinput.suspend();
+
+        for device in self.backends.values_mut() {
+            device.drm.pause();
+        }
+    }
+
+    /// Take back the DRM devices and input when the session is activated again,
+    /// connectors are rescanned since monitors may have changed while we were away.
+    pub fn resume(&mut self, state: &mut State) {
+        info!("resuming session");
+        if self.libinput.resume().is_err() {
+            error!("failed to resume libinput context");
+        }
+
+        let nodes: Vec<_> = self.backends.keys().copied().collect();
+        for node in nodes {
+            let Some(device) = self.backends.get_mut(&node) else {
+                continue;
+            };
+
+            if let Err(err) = device.drm.activate(false) {
+                error!("failed to activate drm device {node}: {err}");
+            }
+
+            for surface in device.surfaces.values_mut() {
+                // Force a full redraw, the content of the crtc is unknown after a VT switch
+                if let Err(err) = surface.compositor.reset_state() {
+                    warn!("failed to reset drm surface state: {err}");
+                }
+
+                surface.frame_pending = false;
+            }
+
+            self.device_changed(node, state);
+        }
+
+        self.render(state);
+    }
+
+    pub fn change_vt(&mut self, vt: i32) {
+        if let Err(err) = self.session.change_vt(vt) {
+            error!("failed to switch to vt {vt}: {err}");
+        }
+    }
+
+    /// Turn the monitors off by disabling their crtc, the next rendered frame turns them back on
+    pub fn set_monitors_active(&mut self, active: bool) {
+        self.monitors_active = active;
+
+        if active {
+            let surfaces: Vec<_> = self
+                .backends
+                .iter()
+                .flat_map(|(node, device)| device.surfaces.keys().map(|crtc| (*node, *crtc)))
+                .collect();
+
+            for (node, crtc) in surfaces {
+                self.schedule_render(node, crtc, Duration::ZERO);
+            }
+
+            return;
+        }
+
+        for device in self.backends.values_mut() {
+            for surface in device.surfaces.values_mut() {
+                if let Err(err) = surface.compositor.clear() {
+                    warn!("failed to clear drm surface: {err}");
+                }
+
+                surface.frame_pending = false;
+            }
+        }
+    }
+
+    /// Render every output which is not waiting for a VBlank. Outputs are then redrawn
+    /// after each VBlank, or after a refresh interval when nothing changed.
+    pub fn render(&mut self, state: &mut State) {
+        let surfaces: Vec<_> = self
+            .backends
+            .iter()
+            .flat_map(|(node, device)| device.surfaces.keys().map(|crtc| (*node, *crtc)))
+            .collect();
+
+        for (node, crtc) in surfaces {
+            self.render_surface(node, crtc, state);
+        }
+    }
+
+    fn render_surface(&mut self, node: DrmNode, crtc: crtc::Handle, state: &mut State) {
+        if !self.session.is_active() || !self.monitors_active {
+            return;
+        }
+
+        let Some(device) = self.backends.get_mut(&node) else {
+            return;
+        };
+
+        let Some(surface) = device.surfaces.get_mut(&crtc) else {
+            return;
+        };
+
+        if surface.frame_pending {
+            return;
+        }
+
+        let mut renderer = match self.gpus.single_renderer(&device.render_node) {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                warn!("failed to get renderer: {err}");
+                return;
+            }
+        };
+
+        // The cursor goes first so that it is drawn on top of the windows
+        let elements: Vec<OutputRenderElements<_, _>> = state
+            .cursor_elements(&mut renderer, &surface.output)
+            .into_iter()
+            .map(OutputRenderElements::Pointer)
+            .chain(
+                state
+                    .output_elements(&mut renderer, &surface.output)
+                    .into_iter()
+                    .map(OutputRenderElements::Space),
+            )
+            .collect();
+
+        let submitted = match surface.compositor.render_frame::<_, _, GlesTexture>(
+            &mut renderer,
+            &elements,
+            [0.1, 0.1, 0.1, 1.0],
+        ) {
+            Ok(result) if !result.is_empty => match surface.compositor.queue_frame(()) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!("failed to queue frame: {err}");
+                    false
+                }
+            },
+            Ok(_) => false,
+            Err(err) => {
+                warn!("failed to render frame: {err}");
+                false
+            }
+        };
+
+        surface.frame_pending = submitted;
+        let output = surface.output.clone();
+        drop(renderer);
+
+        if !submitted {
+            // Without a VBlank to wait for, check for damage again after a refresh
+            self.schedule_render(node, crtc, refresh_interval(&output));
+            return;
+        }
+
+        // Clients only get frame callbacks for frames actually shown, throttling them
+        // to the refresh rate
+        state.space.elements().for_each(|window| {
+            window.send_frame(
+                &output,
+                state.start_time.elapsed(),
+                Some(Duration::ZERO),
+                |_, _| Some(output.clone()),
+            )
+        });
+
+        state.space.refresh();
+        state.popups.cleanup();
+        let _ = state.display_handle.flush_clients();
+    }
+
+    pub fn seat_name(&self) -> String {
+        self.session.seat()
+    }
+
+    /// Returns the first output driven by this backend, if any
+    pub fn output(&self) -> Option<&Output> {
+        self.backends
+            .values()
+            .flat_map(|device| device.surfaces.values())
+            .map(|surface| &surface.output)
+            .next()
+    }
+
+    pub fn renderer(&mut self) -> Option<UdevRenderer<'_>> {
+        self.gpus.single_renderer(&self.primary_gpu).ok()
+    }
+
+    pub fn import_dmabuf(&mut self, dmabuf: &smithay::backend::allocator::dmabuf::Dmabuf) -> bool {
+        self.gpus
+            .single_renderer(&self.primary_gpu)
+            .and_then(|mut renderer| renderer.import_dmabuf(dmabuf, None))
+            .is_ok()
+    }
+}
+
+/// Apply the settings matching the device, the ones it doesn't support are skipped
+fn configure_pointer(device: &mut InputDevice, config: &[InputConfig]) {
+    let name = device.name().to_string();
+    let config = input::device_config(config, &name);
+    debug!("configuring input device {name}: {config:?}");
+
+    let mut results = vec![];
+    if let Some(profile) = config.accel_profile {
+        results.push(device.config_accel_set_profile(match profile {
+            AccelProfile::Flat => LibinputAccelProfile::Flat,
+            AccelProfile::Adaptive => LibinputAccelProfile::Adaptive,
+        }));
+    }
+
+    if let Some(speed) = config.accel_speed {
+        results.push(device.config_accel_set_speed(speed));
+    }
+
+    if let Some(natural_scroll) = config.natural_scroll {
+        results.push(device.config_scroll_set_natural_scroll_enabled(natural_scroll));
+    }
+
+    if let Some(tap) = config.tap {
+        results.push(device.config_tap_set_enabled(tap));
+    }
+
+    if let Some(left_handed) = config.left_handed {
+        results.push(device.config_left_handed_set(left_handed));
+    }
+
+    if let Some(dwt) = config.disable_while_typing {
+        results.push(device.config_dwt_set_enabled(dwt));
+    }
+
+    if let Some(method) = config.scroll_method {
+        results.push(device.config_scroll_set_method(match method {
+            ScrollMethod::NoScroll => LibinputScrollMethod::NoScroll,
+            ScrollMethod::TwoFinger => LibinputScrollMethod::TwoFinger,
+            ScrollMethod::Edge => LibinputScrollMethod::Edge,
+            ScrollMethod::OnButtonDown => LibinputScrollMethod::OnButtonDown,
+        }));
+    }
+
+    for err in results.into_iter().filter_map(Result::err) {
+        debug!("input device {name} setting not applied: {err:?}");
+    }
+}
+
+/// Output name as exposed to clients, e.g. `DP-1` or `HDMI-A-2`
+fn connector_name(interface: connector::Interface, interface_id: u32) -> String {
+    format!("{}-{}", interface.as_str(), interface_id)
+}
+
+/// Outputs to add or remove following a connector scan. Disconnections come first so that
+/// a crtc moved to another connector is released before being reused, connectors without
+/// a crtc or whose crtc already drives an output are skipped.
+fn output_changes<C: fmt::Debug>(
+    events: Vec<ConnectorEvent<C>>,
+    active: &[crtc::Handle],
+) -> Vec<OutputChange<C>> {
+    let (connected, disconnected): (Vec<_>, Vec<_>) = events
+        .into_iter()
+        .partition(|event| matches!(event, ConnectorEvent::Connected(..)));
+
+    let mut busy = active.to_vec();
+    let mut changes = vec![];
+    for event in disconnected.into_iter().chain(connected) {
+        match event {
+            ConnectorEvent::Disconnected(connector, Some(crtc)) => {
+                busy.retain(|active| *active != crtc);
+                changes.push(OutputChange::Remove { connector, crtc });
+            }
+            ConnectorEvent::Connected(connector, Some(crtc)) if !busy.contains(&crtc) => {
+                busy.push(crtc);
+                changes.push(OutputChange::Add { connector, crtc });
+            }
+            ConnectorEvent::Connected(connector, crtc) => {
+                warn!(?crtc, "no free crtc for connector {connector:?}");
+            }
+            ConnectorEvent::Disconnected(_, None) => {}
+        }
+    }
+
+    changes
+}
+
+/// Duration of a frame in the current mode of the output, 60Hz if unknown
+fn refresh_interval(output: &Output) -> Duration {
+    output
+        .current_mode()
+        .map(|mode| mode.refresh)
+        .filter(|refresh| *refresh > 0)
+        .map_or(Duration::from_micros(16_667), |refresh| {
+            Duration::from_secs_f64(1_000.0 / refresh as f64)
+        })
+}
+
+/// New outputs are placed horizontally, to the right of the existing ones
+fn next_output_location(
+    geometries: impl Iterator<Item = Rectangle<i32, Logical>>,
+) -> Point<i32, Logical> {
+    let x = geometries
+        .map(|geometry| geometry.loc.x + geometry.size.w)
+        .max()
+        .unwrap_or(0);
+
+    (x, 0).into()
+}
+
+#[cfg(test)]
+mod test {
+    use smithay::reexports::drm::control::{self, connector, crtc};
+    use smithay::utils::{Point, Rectangle};
+
+    use crate::backend::udev::{
+        connector_name, next_output_location, output_changes, ConnectorEvent, OutputChange,
+    };
+
+    fn crtc(id: u32) -> crtc::Handle {
+        control::from_u32(id).unwrap()
+    }
+
+    #[test]
+    fn should_format_connector_name() {
+        assert_eq!(connector_name(connector::Interface::DisplayPort, 1), "DP-1");
+        assert_eq!(connector_name(connector::Interface::HDMIA, 2), "HDMI-A-2");
+        assert_eq!(
+            connector_name(connector::Interface::EmbeddedDisplayPort, 1),
+            "eDP-1"
+        );
+    }
+
+    #[test]
+    fn should_place_outputs_side_by_side() {
+        assert_eq!(
+            next_output_location(std::iter::empty()),
+            Point::from((0, 0))
+        );
+
+        let outputs = [
+            Rectangle::from_loc_and_size((0, 0), (1920, 1080)),
+            Rectangle::from_loc_and_size((1920, 0), (2560, 1440)),
+        ];
+
+        assert_eq!(
+            next_output_location(outputs.into_iter()),
+            Point::from((4480, 0))
+        );
+    }
+
+    #[test]
+    fn should_add_and_remove_outputs_of_scanned_connectors() {
+        let events = vec![
+            ConnectorEvent::Connected("DP-1", Some(crtc(1))),
+            ConnectorEvent::Disconnected("HDMI-A-1", Some(crtc(2))),
+        ];
+
+        assert_eq!(
+            output_changes(events, &[crtc(2)]),
+            vec![
+                OutputChange::Remove {
+                    connector: "HDMI-A-1",
+                    crtc: crtc(2)
+                },
+                OutputChange::Add {
+                    connector: "DP-1",
+                    crtc: crtc(1)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_reuse_crtc_released_by_disconnected_connector() {
+        let events = vec![
+            ConnectorEvent::Connected("DP-2", Some(crtc(1))),
+            ConnectorEvent::Disconnected("DP-1", Some(crtc(1))),
+        ];
+
+        assert_eq!(
+            output_changes(events, &[crtc(1)]),
+            vec![
+                OutputChange::Remove {
+                    connector: "DP-1",
+                    crtc: crtc(1)
+                },
+                OutputChange::Add {
+                    connector: "DP-2",
+                    crtc: crtc(1)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_skip_connectors_without_free_crtc() {
+        let events = vec![
+            ConnectorEvent::Connected("DP-1", None),
+            ConnectorEvent::Connected("DP-2", Some(crtc(1))),
+            ConnectorEvent::Connected("DP-3", Some(crtc(2))),
+            ConnectorEvent::Connected("DP-4", Some(crtc(2))),
+            ConnectorEvent::Disconnected("DP-5", None),
+        ];
+
+        assert_eq!(
+            output_changes(events, &[crtc(1)]),
+            vec![OutputChange::Add {
+                connector: "DP-3",
+                crtc: crtc(2)
+            }]
+        );
+    }
+}
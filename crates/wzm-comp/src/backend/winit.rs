@@ -14,7 +14,8 @@ use smithay::utils::Rectangle;
 use smithay::wayland::dmabuf::{DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufState};
 use tracing::{info, warn};
 
-use crate::decoration::{BorderShader, CustomRenderElements};
+use crate::backend::OutputRenderElements;
+use crate::decoration::BorderShader;
 use crate::{DisplayHandle, State, Wzm};
 
 pub struct Winit {
@@ -35,6 +36,8 @@ impl Winit {
             .with_title("wzm");
 
         let (mut backend, winit) = winit::init_from_builder::<GlesRenderer>(builder)?;
+        // The cursor is drawn by the compositor
+        backend.window().set_cursor_visible(false);
         BorderShader::init(backend.renderer());
 
         if backend.renderer().bind_wl_display(&display_handle).is_ok() {
@@ -133,19 +136,23 @@ impl Winit {
         let size = self.backend.window_size();
         let damage = Rectangle::from_loc_and_size((0, 0), size);
 
+        let renderer = self.backend.renderer();
+        let elements: Vec<OutputRenderElements<_, _>> = wzm
+            .cursor_elements(renderer, &self.output)
+            .into_iter()
+            .map(OutputRenderElements::Pointer)
+            .chain(
+                wzm.output_elements(renderer, &self.output)
+                    .into_iter()
+                    .map(OutputRenderElements::Space),
+            )
+            .collect();
+
         self.backend.bind().unwrap();
 
-        smithay::desktop::space::render_output::<_, CustomRenderElements<GlesRenderer>, _, _>(
-            &self.output,
-            self.backend.renderer(),
-            1.0,
-            0,
-            [&wzm.space],
-            &[],
-            &mut self.damage_tracker,
-            [0.1, 0.1, 0.1, 1.0],
-        )
-        .unwrap();
+        self.damage_tracker
+            .render_output(self.backend.renderer(), 0, &elements, [0.1, 0.1, 0.1, 1.0])
+            .unwrap();
 
         self.backend.submit(Some(&[damage])).unwrap();
 
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::element::memory::{
+    MemoryRenderBuffer, MemoryRenderBufferRenderElement,
+};
+use smithay::backend::renderer::element::surface::{
+    render_elements_from_surface_tree, WaylandSurfaceRenderElement,
+};
+use smithay::backend::renderer::element::Kind;
+use smithay::backend::renderer::{ImportAll, ImportMem, Renderer};
+use smithay::input::pointer::{CursorIcon, CursorImageAttributes, CursorImageStatus};
+use smithay::utils::{IsAlive, Logical, Physical, Point, Scale, Transform};
+use smithay::wayland::compositor::with_states;
+use tracing::warn;
+use xcursor::parser::{parse_xcursor, Image};
+use xcursor::CursorTheme;
+
+use wzm_config::CursorConfig;
+
+const DEFAULT_THEME: &str = "default";
+const DEFAULT_SIZE: u32 = 24;
+
+smithay::render_elements! {
+    pub PointerRenderElement<R> where R: ImportAll + ImportMem;
+    Surface=WaylandSurfaceRenderElement<R>,
+    Memory=MemoryRenderBufferRenderElement<R>,
+}
+
+/// Server side cursor, drawn from the xcursor theme or from the client cursor surface
+pub struct Cursor {
+    theme: CursorTheme,
+    theme_name: String,
+    size: u32,
+    images: HashMap<CursorIcon, Vec<Image>>,
+    buffers: HashMap<(CursorIcon, usize), MemoryRenderBuffer>,
+    /// Cursor requested by the client having the pointer focus
+    pub status: CursorImageStatus,
+    /// Shown instead of the client cursor during a move or resize grab
+    pub grab_icon: Option<CursorIcon>,
+}
+
+impl Cursor {
+    /// Load the theme from the config, falling back to `XCURSOR_THEME` and `XCURSOR_SIZE`
+    pub fn load(config: &CursorConfig) -> Self {
+        let theme_name = config
+            .theme
+            .clone()
+            .or_else(|| env::var("XCURSOR_THEME").ok())
+            .unwrap_or_else(|| DEFAULT_THEME.to_string());
+
+        let size = config
+            .size
+            .or_else(|| env::var("XCURSOR_SIZE").ok()?.parse().ok())
+            .unwrap_or(DEFAULT_SIZE);
+
+        Self {
+            theme: CursorTheme::load(&theme_name),
+            theme_name,
+            size,
+            images: HashMap::new(),
+            buffers: HashMap::new(),
+            status: CursorImageStatus::Named(CursorIcon::Default),
+            grab_icon: None,
+        }
+    }
+
+    /// Switch to the theme and size of the config, keeping the current cursor image
+    pub fn reload(&mut self, config: &CursorConfig) {
+        let status = std::mem::replace(&mut self.status, CursorImageStatus::Hidden);
+        let grab_icon = self.grab_icon.take();
+        *self = Self::load(config);
+        self.status = status;
+        self.grab_icon = grab_icon;
+    }
+
+    pub fn theme_name(&self) -> &str {
+        &self.theme_name
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Elements drawing the cursor with its hotspot at `location`, in output coordinates
+    pub fn render_elements<R>(
+        &mut self,
+        renderer: &mut R,
+        location: Point<f64, Logical>,
+        scale: Scale<f64>,
+        time: Duration,
+    ) -> Vec<PointerRenderElement<R>>
+    where
+        R: Renderer + ImportAll + ImportMem,
+        R::TextureId: Clone + 'static,
+    {
+        let icon = match (self.grab_icon, &self.status) {
+            (Some(icon), _) => icon,
+            (None, CursorImageStatus::Hidden) => return vec![],
+            (None, CursorImageStatus::Named(icon)) => *icon,
+            (None, CursorImageStatus::Surface(surface)) if surface.alive() => {
+                let hotspot = with_states(surface, |states| {
+                    states
+                        .data_map
+                        .get::<Mutex<CursorImageAttributes>>()
+                        .map(|attributes| attributes.lock().unwrap().hotspot)
+                        .unwrap_or_default()
+                });
+
+                let location: Point<i32, Physical> =
+                    (location - hotspot.to_f64()).to_physical_precise_round(scale);
+
+                return render_elements_from_surface_tree(
+                    renderer,
+                    surface,
+                    location,
+                    scale,
+                    1.0,
+                    Kind::Cursor,
+                );
+            }
+            (None, CursorImageStatus::Surface(_)) => CursorIcon::Default,
+        };
+
+        self.load_icon(icon);
+        let images = &self.images[&icon];
+        let idx = frame(time.as_millis() as u32, self.size, images);
+        let image = &images[idx];
+
+        let buffer = self.buffers.entry((icon, idx)).or_insert_with(|| {
+            MemoryRenderBuffer::from_slice(
+                &image.pixels_rgba,
+                Fourcc::Abgr8888,
+                (image.width as i32, image.height as i32),
+                1,
+                Transform::Normal,
+                None,
+            )
+        });
+
+        let hotspot = Point::<f64, Logical>::from((image.xhot as f64, image.yhot as f64));
+        let location = (location - hotspot).to_physical(scale);
+
+        match MemoryRenderBufferRenderElement::from_buffer(
+            renderer,
+            location,
+            buffer,
+            None,
+            None,
+            None,
+            Kind::Cursor,
+        ) {
+            Ok(element) => vec![element.into()],
+            Err(err) => {
+                warn!("failed to render cursor: {err:?}");
+                vec![]
+            }
+        }
+    }
+
+    /// Load the images of the icon, the default cursor is used for the ones missing
+    /// in the theme
+    fn load_icon(&mut self, icon: CursorIcon) {
+        if self.images.contains_key(&icon) {
+            return;
+        }
+
+        let images = match load_images(&self.theme, icon) {
+            Some(images) => images,
+            None if icon != CursorIcon::Default => {
+                warn!(
+                    "no '{}' cursor in the {} theme",
+                    icon.name(),
+                    self.theme_name
+                );
+                self.load_icon(CursorIcon::Default);
+                self.images[&CursorIcon::Default].clone()
+            }
+            None => {
+                warn!("no default cursor in the {} theme", self.theme_name);
+                vec![fallback_image()]
+            }
+        };
+
+        self.images.insert(icon, images);
+    }
+}
+
+fn load_images(theme: &CursorTheme, icon: CursorIcon) -> Option<Vec<Image>> {
+    std::iter::once(icon.name())
+        .chain(icon.alt_names().iter().copied())
+        .find_map(|name| theme.load_icon(name))
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|data| parse_xcursor(&data))
+        .filter(|images| !images.is_empty())
+}
+
+/// Index of the image to show, among the ones with the size nearest to the requested one.
+/// Animated cursors loop over their frames according to each frame delay.
+fn frame(millis: u32, size: u32, images: &[Image]) -> usize {
+    let nearest = images
+        .iter()
+        .min_by_key(|image| (size as i32 - image.size as i32).abs())
+        .map(|image| image.size)
+        .unwrap_or_default();
+
+    let frames: Vec<_> = images
+        .iter()
+        .enumerate()
+        .filter(|(_, image)| image.size == nearest)
+        .collect();
+
+    let total: u32 = frames.iter().map(|(_, image)| image.delay).sum();
+    if total == 0 {
+        return frames.first().map(|(idx, _)| *idx).unwrap_or_default();
+    }
+
+    let mut millis = millis % total;
+    for (idx, image) in &frames {
+        if millis < image.delay {
+            return *idx;
+        }
+
+        millis -= image.delay;
+    }
+
+    0
+}
+
+/// A plain arrow, for systems without any cursor theme
+fn fallback_image() -> Image {
+    const SIZE: u32 = 16;
+
+    let mut pixels_rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let pixel = if x > y {
+                [0, 0, 0, 0]
+            } else if x == 0 || x == y || y == SIZE - 1 {
+                [0, 0, 0, 255]
+            } else {
+                [255, 255, 255, 255]
+            };
+
+            pixels_rgba.extend_from_slice(&pixel);
+        }
+    }
+
+    let pixels_argb = pixels_rgba
+        .chunks(4)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect();
+
+    Image {
+        size: SIZE,
+        width: SIZE,
+        height: SIZE,
+        xhot: 0,
+        yhot: 0,
+        delay: 0,
+        pixels_rgba,
+        pixels_argb,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use xcursor::parser::Image;
+
+    use crate::cursor::frame;
+
+    fn image(size: u32, delay: u32) -> Image {
+        Image {
+            size,
+            width: size,
+            height: size,
+            xhot: 0,
+            yhot: 0,
+            delay,
+            pixels_rgba: vec![],
+            pixels_argb: vec![],
+        }
+    }
+
+    #[test]
+    fn should_pick_nearest_size() {
+        let images = [image(24, 0), image(32, 0), image(48, 0)];
+
+        assert_eq!(frame(0, 24, &images), 0);
+        assert_eq!(frame(0, 30, &images), 1);
+        assert_eq!(frame(0, 64, &images), 2);
+    }
+
+    #[test]
+    fn should_show_first_frame_when_delays_are_zero() {
+        let images = [image(24, 0), image(32, 0), image(32, 0)];
+
+        assert_eq!(frame(1234, 32, &images), 1);
+    }
+
+    #[test]
+    fn should_loop_over_animation_frames() {
+        let images = [
+            image(24, 100),
+            image(24, 50),
+            image(32, 100),
+            image(32, 100),
+        ];
+
+        assert_eq!(frame(0, 24, &images), 0);
+        assert_eq!(frame(99, 24, &images), 0);
+        assert_eq!(frame(100, 24, &images), 1);
+        assert_eq!(frame(149, 24, &images), 1);
+        assert_eq!(frame(150, 24, &images), 0);
+        assert_eq!(frame(150, 32, &images), 3);
+        assert_eq!(frame(250, 32, &images), 2);
+    }
+
+    #[test]
+    fn should_default_without_images() {
+        assert_eq!(frame(0, 24, &[]), 0);
+    }
+}
@@ -1,12 +1,12 @@
 use smithay::desktop::Window;
 use smithay::input::pointer::{
-    AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
-    GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
-    GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab,
-    PointerInnerHandle, RelativeMotionEvent,
+    AxisFrame, ButtonEvent, CursorIcon, GestureHoldBeginEvent, GestureHoldEndEvent,
+    GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent,
+    GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData,
+    MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
 };
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
-use smithay::utils::{Logical, Point};
+use smithay::utils::{Logical, Point, Rectangle};
 
 use crate::Wzm;
 
@@ -26,17 +26,20 @@ impl PointerGrab<Wzm> for MoveSurfaceGrab {
     ) {
         // While the grab is active, no client has pointer focus
         handle.motion(data, None, event);
+        data.state.cursor.grab_icon = Some(CursorIcon::Grabbing);
 
         let delta = event.location - self.start_data.location;
-        let new_location = self.initial_window_location.to_f64() + delta;
-        let tree = data.state.get_current_workspace();
-        // let ws = tree.get_mut();
-        // let (_, w) = ws.get_focus();
-        // let wrap = w.unwrap();
-        // wrap.update_loc(new_location.to_i32_round());
-        // debug!("moving window to {new_location:?}");
-
-        // wrap.map(&mut data.space, true);
+        let new_location = (self.initial_window_location.to_f64() + delta).to_i32_round();
+        let ws = data.state.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+
+        if let Some(geometry) = ws.floating_geometry(&self.window) {
+            let geometry = Rectangle::from_loc_and_size(new_location, geometry.size);
+            ws.set_floating_geometry(&self.window, geometry);
+            data.state
+                .space
+                .map_element(self.window.clone(), new_location, true);
+        }
     }
 
     fn relative_motion(
@@ -151,5 +154,7 @@ impl PointerGrab<Wzm> for MoveSurfaceGrab {
         &self.start_data
     }
 
-    fn unset(&mut self, _data: &mut Wzm) {}
+    fn unset(&mut self, data: &mut Wzm) {
+        data.state.cursor.grab_icon = None;
+    }
 }
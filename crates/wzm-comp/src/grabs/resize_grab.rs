@@ -1,10 +1,10 @@
 use crate::Wzm;
 use smithay::desktop::{Space, Window};
 use smithay::input::pointer::{
-    AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
-    GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
-    GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab,
-    PointerInnerHandle, RelativeMotionEvent,
+    AxisFrame, ButtonEvent, CursorIcon, GestureHoldBeginEvent, GestureHoldEndEvent,
+    GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent,
+    GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData,
+    MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
 };
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
@@ -29,6 +29,22 @@ bitflags::bitflags! {
     }
 }
 
+impl ResizeEdge {
+    pub fn cursor_icon(&self) -> CursorIcon {
+        match *self {
+            Self::TOP => CursorIcon::NResize,
+            Self::BOTTOM => CursorIcon::SResize,
+            Self::LEFT => CursorIcon::WResize,
+            Self::RIGHT => CursorIcon::EResize,
+            Self::TOP_LEFT => CursorIcon::NwResize,
+            Self::TOP_RIGHT => CursorIcon::NeResize,
+            Self::BOTTOM_LEFT => CursorIcon::SwResize,
+            Self::BOTTOM_RIGHT => CursorIcon::SeResize,
+            _ => CursorIcon::Move,
+        }
+    }
+}
+
 impl From<xdg_toplevel::ResizeEdge> for ResizeEdge {
     #[inline]
     fn from(x: xdg_toplevel::ResizeEdge) -> Self {
@@ -82,6 +98,7 @@ impl PointerGrab<Wzm> for ResizeSurfaceGrab {
     ) {
         // While the grab is active, no client has pointer focus
         handle.motion(data, None, event);
+        data.state.cursor.grab_icon = Some(self.edges.cursor_icon());
 
         let mut delta = event.location - self.start_data.location;
 
@@ -262,7 +279,9 @@ impl PointerGrab<Wzm> for ResizeSurfaceGrab {
         &self.start_data
     }
 
-    fn unset(&mut self, _data: &mut Wzm) {}
+    fn unset(&mut self, data: &mut Wzm) {
+        data.state.cursor.grab_icon = None;
+    }
 }
 
 /// State of the resize operation.
@@ -42,8 +42,14 @@ impl CompositorHandler for Wzm {
         };
 
         self.state.layer_shell_handle_commit(surface);
+        if self.state.place_toplevel(surface) {
+            // A window rule sent the window to another workspace
+            let focus = self.state.get_current_workspace().borrow().get_focus();
+            self.set_keyboard_focus(focus.as_ref());
+        }
         xdg_shell::handle_commit(&mut self.state.popups, &self.state.space, surface);
         resize_grab::handle_commit(&mut self.state.space, surface);
+        self.state.update_floating_geometry(surface);
     }
 
     fn destroyed(&mut self, _: &WlSurface) {
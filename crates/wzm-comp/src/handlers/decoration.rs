@@ -1,23 +1,51 @@
 use smithay::delegate_xdg_decoration;
 use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode;
+use smithay::wayland::compositor::with_states;
 use smithay::wayland::shell::xdg::decoration::XdgDecorationHandler;
-use smithay::wayland::shell::xdg::ToplevelSurface;
+use smithay::wayland::shell::xdg::{ToplevelSurface, XdgToplevelSurfaceData};
 
 use crate::Wzm;
 
 impl XdgDecorationHandler for Wzm {
     fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        let mode = self.state.decoration_mode(toplevel.wl_surface());
         toplevel.with_pending_state(|state| {
-            state.decoration_mode = Some(Mode::ServerSide);
+            state.decoration_mode = Some(mode);
         });
     }
 
-    fn request_mode(&mut self, _toplevel: ToplevelSurface, _mode: Mode) {
-        //TODO
+    /// The mode is decided by the compositor and window rules, the client is answered
+    /// with it whatever it asks for
+    fn request_mode(&mut self, toplevel: ToplevelSurface, _mode: Mode) {
+        self.configure_decoration(&toplevel);
     }
 
-    fn unset_mode(&mut self, _toplevel: ToplevelSurface) {
-        // TODO
+    fn unset_mode(&mut self, toplevel: ToplevelSurface) {
+        self.configure_decoration(&toplevel);
+    }
+}
+
+impl Wzm {
+    fn configure_decoration(&mut self, toplevel: &ToplevelSurface) {
+        let mode = self.state.decoration_mode(toplevel.wl_surface());
+        toplevel.with_pending_state(|state| {
+            state.decoration_mode = Some(mode);
+        });
+
+        let initial_configure_sent = with_states(toplevel.wl_surface(), |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        });
+
+        // Before the initial configure the mode goes out with it
+        if initial_configure_sent {
+            toplevel.send_pending_configure();
+        }
     }
 }
 
@@ -33,6 +33,10 @@ impl WlrLayerShellHandler for Wzm {
     }
 
     fn layer_destroyed(&mut self, surface: WlrLayerSurface) {
+        self.state
+            .hidden_layers
+            .retain(|(_, layer)| layer.layer_surface() != &surface);
+
         if let Some((mut map, layer)) = self.state.space.outputs().find_map(|o| {
             let map = layer_map_for_output(o);
             let layer = map
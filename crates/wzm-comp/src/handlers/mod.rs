@@ -1,3 +1,4 @@
+use smithay::input::pointer::CursorImageStatus;
 use smithay::input::{Seat, SeatHandler, SeatState};
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::Resource;
@@ -10,6 +11,8 @@ use smithay::wayland::selection::SelectionHandler;
 use smithay::wayland::xdg_foreign::{XdgForeignHandler, XdgForeignState};
 use smithay::{delegate_data_device, delegate_output, delegate_seat, delegate_xdg_foreign};
 
+use wzm_config::ipc::Event;
+
 use crate::Wzm;
 
 mod activation;
@@ -32,17 +35,18 @@ impl SeatHandler for Wzm {
         &mut self.state.seat_state
     }
 
-    fn cursor_image(
-        &mut self,
-        _seat: &Seat<Self>,
-        _image: smithay::input::pointer::CursorImageStatus,
-    ) {
+    fn cursor_image(&mut self, _seat: &Seat<Self>, image: CursorImageStatus) {
+        self.state.cursor.status = image;
     }
 
     fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
         let dh = &self.state.display_handle;
         let client = focused.and_then(|s| dh.get_client(s.id()).ok());
         set_data_device_focus(dh, seat, client);
+
+        let window = focused.and_then(|surface| self.state.window_for_surface(surface));
+        let info = window.map(|window| self.state.window_info(&window));
+        self.state.emit_event(Event::WindowFocused(info));
     }
 }
 
@@ -5,6 +5,7 @@ use smithay::desktop::{
 use smithay::input::pointer::Focus;
 use smithay::input::Seat;
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
+use smithay::reexports::wayland_server::protocol::wl_output::WlOutput;
 use smithay::reexports::wayland_server::protocol::wl_seat;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::utils::{Rectangle, Serial, SERIAL_COUNTER};
@@ -14,8 +15,11 @@ use smithay::wayland::shell::xdg::{
     XdgShellState, XdgToplevelSurfaceData,
 };
 
+use wzm_config::ipc::Event;
+
 use crate::grabs::{MoveSurfaceGrab, ResizeSurfaceGrab};
 use crate::input::check_grab;
+use crate::state::is_placed;
 use crate::{State, Wzm};
 
 impl XdgShellHandler for Wzm {
@@ -31,12 +35,20 @@ impl XdgShellHandler for Wzm {
         let next_layout = self.state.next_layout;
 
         match next_layout {
-            Some(next_layout) => workspace.split_insert(window, next_layout),
-            None => workspace.insert(window),
+            Some(next_layout) => workspace.split_insert(window.clone(), next_layout),
+            None => workspace.insert(window.clone()),
         }
 
+        drop(workspace);
+        // Subscribers are told about the window on its initial commit, once placed by window rules
         self.state.next_layout = None;
 
+        // LayoutHorizontal and LayoutVertical apply to the container of the new window
+        if let Some(orientation) = next_layout {
+            self.state
+                .emit_event(Event::LayoutChanged(orientation.into()));
+        }
+
         let handle = self
             .state
             .seat
@@ -47,6 +59,34 @@ impl XdgShellHandler for Wzm {
         handle.set_focus(self, Some(surface.wl_surface().clone()), serial);
     }
 
+    /// Windows closed with the `CloseWindow` action are already gone from their workspace,
+    /// this handles the ones closed by their client
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        let Some(window) = self.state.window_for_surface(surface.wl_surface()) else {
+            return;
+        };
+
+        let info = self.state.window_info(&window);
+        let ws = self
+            .state
+            .workspaces
+            .values()
+            .find(|ws| ws.borrow().leaves().contains(&window))
+            .cloned();
+
+        if let Some(ws) = ws {
+            ws.borrow_mut().remove_matching(&window);
+        }
+
+        self.state.space.unmap_elem(&window);
+        if is_placed(&window) {
+            self.state.emit_event(Event::WindowClosed(info));
+        }
+
+        let focus = self.state.get_current_workspace().borrow().get_focus();
+        self.set_keyboard_focus(focus.as_ref());
+    }
+
     fn new_popup(&mut self, surface: PopupSurface, _positioner: PositionerState) {
         self.state.unconstrain_popup(&surface);
         let _ = self.state.popups.track_popup(PopupKind::Xdg(surface));
@@ -66,6 +106,12 @@ impl XdgShellHandler for Wzm {
                 .find(|w| w.toplevel().unwrap().wl_surface() == wl_surface)
                 .unwrap()
                 .clone();
+
+            // Tiled windows are positioned by the tree
+            if !self.state.is_floating(&window) {
+                return;
+            }
+
             let initial_window_location = self.state.space.element_location(&window).unwrap();
 
             let grab = MoveSurfaceGrab {
@@ -99,6 +145,12 @@ impl XdgShellHandler for Wzm {
                 .find(|w| w.toplevel().unwrap().wl_surface() == wl_surface)
                 .unwrap()
                 .clone();
+
+            // Tiled windows are positioned by the tree
+            if !self.state.is_floating(&window) {
+                return;
+            }
+
             let initial_window_location = self.state.space.element_location(&window).unwrap();
             let initial_window_size = window.geometry().size;
 
@@ -119,6 +171,46 @@ impl XdgShellHandler for Wzm {
         }
     }
 
+    fn fullscreen_request(&mut self, surface: ToplevelSurface, _output: Option<WlOutput>) {
+        let Some(geometry) = self.state.fullscreen_geometry() else {
+            return;
+        };
+
+        let ws = self.state.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+        let Some(window) = ws
+            .leaves()
+            .into_iter()
+            .find(|w| w.toplevel().unwrap().wl_surface() == surface.wl_surface())
+        else {
+            return;
+        };
+
+        if ws.fullscreen_window().as_ref() != Some(&window) {
+            ws.set_focus_matching(&window);
+            ws.toggle_fullscreen_window(geometry);
+        }
+    }
+
+    fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
+        let ws = self.state.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+        let is_fullscreen = ws
+            .fullscreen_window()
+            .is_some_and(|w| w.toplevel().unwrap().wl_surface() == surface.wl_surface());
+
+        if is_fullscreen {
+            ws.exit_fullscreen();
+        }
+    }
+
+    fn title_changed(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.state.window_for_surface(surface.wl_surface()) {
+            let info = self.state.window_info(&window);
+            self.state.emit_event(Event::WindowTitleChanged(info));
+        }
+    }
+
     fn grab(&mut self, _surface: PopupSurface, _seat: wl_seat::WlSeat, _serial: Serial) {
         // TODO popup grabs
     }
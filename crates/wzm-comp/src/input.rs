@@ -1,20 +1,25 @@
+use std::time::Duration;
+
 use smithay::backend::input::{
     AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
     KeyState, KeyboardKeyEvent, MouseButton, PointerAxisEvent, PointerButtonEvent,
+    PointerMotionEvent,
 };
-use smithay::input::keyboard::{FilterResult, Keysym, ModifiersState};
+use smithay::input::keyboard::{FilterResult, ModifiersState};
 use smithay::input::pointer::{
     AxisFrame, ButtonEvent, GrabStartData as PointerGrabStartData, MotionEvent,
 };
 use smithay::input::Seat;
+use smithay::reexports::calloop::timer::{TimeoutAction, Timer};
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::Resource;
-use smithay::utils::{Serial, SERIAL_COUNTER};
+use smithay::utils::{Logical, Point, Serial, SERIAL_COUNTER};
+use tracing::warn;
 use xkbcommon::xkb::keysyms::{KEY_XF86Switch_VT_1, KEY_XF86Switch_VT_12};
 
 use wzm_config::action::KeyAction;
 use wzm_config::keybinding;
-use wzm_config::keybinding::Action;
+use wzm_config::keybinding::{Key, SequenceMatch};
 
 use crate::action::spawn;
 use crate::state::State;
@@ -23,55 +28,23 @@ use crate::Wzm;
 impl Wzm {
     pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>) {
         match event {
-            InputEvent::Keyboard { event } => match self.keyboard_key_to_action::<I>(event) {
-                KeyAction::Resize(kind, direction, amount) if self.state.resize_mode() => {
-                    self.resize(kind, direction, amount)
-                }
-                KeyAction::Run(cmd, env) => spawn(cmd, env),
-                KeyAction::ScaleUp => {}
-                KeyAction::ScaleDown => {}
-                KeyAction::RotateOutput => {}
-                KeyAction::Screen(_) => {}
-                KeyAction::ToggleTint => {}
-                KeyAction::TogglePreview => {}
-                KeyAction::ToggleFullScreenWindow => self.toggle_fullscreen_window(),
-                KeyAction::ToggleFullScreenContainer => {}
-                KeyAction::MoveWindow(direction) => self.move_window(direction),
-                KeyAction::MoveContainer(_) => {}
-                KeyAction::MoveFocus(direction) => self.move_focus(direction),
-                KeyAction::MoveToWorkspace(_) => {}
-                KeyAction::LayoutVertical => self.set_layout_v(),
-                KeyAction::LayoutHorizontal => self.set_layout_h(),
-                KeyAction::ToggleFloating => self.toggle_floating(),
-                KeyAction::VtSwitch(_) => {}
-                KeyAction::CloseWindow => self.close(),
-                KeyAction::Quit => {}
-                KeyAction::None => {}
-                KeyAction::ToggleResize => self.toggle_resize(),
-                KeyAction::ToggleSwitchLayout => self.toggle_layout(),
-                KeyAction::Resize(..) => {
-                    // Noop
-                }
-            },
-            InputEvent::PointerMotion { .. } => {}
+            InputEvent::Keyboard { event } => {
+                let action = self.keyboard_key_to_action::<I>(event);
+                self.handle_key_action(action);
+            }
+            InputEvent::PointerMotion { event, .. } => {
+                let pointer = self.state.seat.get_pointer().unwrap();
+                let location = pointer.current_location();
+                let pos = self
+                    .state
+                    .clamp_to_outputs(location, location + event.delta());
+                self.pointer_motion(pos, event.time_msec());
+            }
             InputEvent::PointerMotionAbsolute { event, .. } => {
                 let output = self.state.space.outputs().next().unwrap();
                 let output_geo = self.state.space.output_geometry(output).unwrap();
                 let pos = event.position_transformed(output_geo.size) + output_geo.loc.to_f64();
-                let serial = SERIAL_COUNTER.next_serial();
-                let pointer = self.state.seat.get_pointer().unwrap();
-                let under = self.state.surface_under(pos);
-
-                pointer.motion(
-                    self,
-                    under,
-                    &MotionEvent {
-                        location: pos,
-                        serial,
-                        time: event.time_msec(),
-                    },
-                );
-                pointer.frame(self);
+                self.pointer_motion(pos, event.time_msec());
             }
             InputEvent::PointerButton { event, .. } => self.handle_pointer_button::<I>(&event),
             InputEvent::PointerAxis { event, .. } => {
@@ -117,13 +90,85 @@ impl Wzm {
         }
     }
 
+    fn pointer_motion(&mut self, pos: Point<f64, Logical>, time: u32) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let pointer = self.state.seat.get_pointer().unwrap();
+        let under = self.state.surface_under(pos);
+
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: pos,
+                serial,
+                time,
+            },
+        );
+        pointer.frame(self);
+    }
+
+    pub fn handle_key_action(&mut self, action: KeyAction) {
+        // Oneshot modes are left as soon as one of their bindings ran
+        let leave_mode = self.state.config.is_oneshot(&self.state.current_mode)
+            && !matches!(
+                action,
+                KeyAction::None
+                    | KeyAction::VtSwitch(_)
+                    | KeyAction::EnterMode(_)
+                    | KeyAction::ExitMode
+                    | KeyAction::ToggleResize
+            );
+
+        match action {
+            KeyAction::Resize(kind, direction, amount) if self.state.resize_mode() => {
+                self.resize(kind, direction, amount)
+            }
+            KeyAction::Run(cmd, env) => spawn(cmd, env),
+            KeyAction::ScaleUp => {}
+            KeyAction::ScaleDown => {}
+            KeyAction::RotateOutput => {}
+            KeyAction::Screen(_) => {}
+            KeyAction::ToggleTint => {}
+            KeyAction::TogglePreview => {}
+            KeyAction::ToggleFullScreenWindow => self.toggle_fullscreen_window(),
+            KeyAction::ToggleFullScreenContainer => self.toggle_fullscreen_container(),
+            KeyAction::MoveWindow(direction) => self.move_window(direction),
+            KeyAction::MoveContainer(direction) => self.move_container(direction),
+            KeyAction::MoveFocus(direction) => self.move_focus(direction),
+            KeyAction::MoveToWorkspace(num) => self.switch_workspace(num),
+            KeyAction::MoveWindowToWorkspace(num) => self.move_window_to_workspace(num),
+            KeyAction::LayoutVertical => self.set_layout_v(),
+            KeyAction::LayoutHorizontal => self.set_layout_h(),
+            KeyAction::ToggleFloating => self.toggle_floating(),
+            KeyAction::VtSwitch(vt) => self.backend.change_vt(vt),
+            KeyAction::CloseWindow => self.close(),
+            KeyAction::Quit => {}
+            KeyAction::Reload => self.reload_config(),
+            KeyAction::CycleKeyboardLayout => self.cycle_keyboard_layout(),
+            KeyAction::None => {}
+            KeyAction::ToggleResize => self.toggle_resize(),
+            KeyAction::ToggleSwitchLayout => self.toggle_layout(),
+            KeyAction::EnterMode(mode) => self.enter_mode(mode),
+            KeyAction::ExitMode => self.exit_mode(),
+            KeyAction::SaveLayout(path) => self.save_layout(path),
+            KeyAction::LoadLayout(path) => self.load_layout(path),
+            KeyAction::Resize(..) => {
+                // Noop
+            }
+        }
+
+        if leave_mode {
+            self.exit_mode();
+        }
+    }
+
     fn keyboard_key_to_action<B: InputBackend>(&mut self, evt: B::KeyboardKeyEvent) -> KeyAction {
         let keycode = evt.key_code();
         let state = evt.state();
         let serial = SERIAL_COUNTER.next_serial();
         let time = Event::time_msec(&evt);
         let keyboard = self.state.seat.get_keyboard().unwrap();
-        let mode = self.state.current_mode;
+        let mode = self.state.current_mode.clone();
 
         keyboard
             .input(
@@ -133,56 +178,117 @@ impl Wzm {
                 serial,
                 time,
                 |app_state, modifiers, key_handle| {
-                    let keysym = key_handle.modified_sym();
+                    let key = Key {
+                        sym: key_handle.modified_sym(),
+                        raw_syms: key_handle.raw_syms().to_vec(),
+                        code: key_handle.raw_code().raw(),
+                    };
+                    let main_modifier = &app_state.state.config.main_modifier;
+                    app_state.state.mod_pressed = main_modifier.is_pressed(modifiers);
+
                     match state {
-                        KeyState::Released if modifiers.alt => {
-                            app_state.state.mod_pressed = false;
-                            FilterResult::Forward
+                        KeyState::Pressed => {
+                            app_state.state.last_pressed_key = Some(key.code);
+                            Self::key_pressed_to_action(&mut app_state.state, modifiers, key, &mode)
                         }
-                        KeyState::Pressed if modifiers.alt => {
-                            app_state.state.mod_pressed = true;
-                            Self::key_pressed_to_action(
-                                &mut app_state.state,
-                                modifiers,
-                                keysym,
-                                mode,
-                            )
+                        KeyState::Released => {
+                            Self::key_released(&mut app_state.state, modifiers, &key, &mode);
+                            FilterResult::Forward
                         }
-                        KeyState::Pressed => Self::key_pressed_to_action(
-                            &mut app_state.state,
-                            modifiers,
-                            keysym,
-                            mode,
-                        ),
-                        _ => FilterResult::Forward,
                     }
                 },
             )
+            .or_else(|| self.state.released_action.take())
             .unwrap_or(KeyAction::None)
     }
 
+    /// Release bindings only fire if no other key was pressed since the key was.
+    /// The release itself is always forwarded so clients don't see the key stuck,
+    /// the action is picked up once the event went through.
+    fn key_released(
+        app_state: &mut State,
+        modifiers: &ModifiersState,
+        key: &Key,
+        mode: &keybinding::Mode,
+    ) {
+        if app_state.last_pressed_key.take() != Some(key.code) {
+            return;
+        }
+
+        app_state.released_action = app_state
+            .config
+            .match_release(*modifiers, key, mode)
+            .map(KeyAction::from);
+    }
+
     fn key_pressed_to_action(
         app_state: &mut State,
         modifiers: &ModifiersState,
-        keysym: Keysym,
-        mode: keybinding::Mode,
+        key: Key,
+        mode: &keybinding::Mode,
     ) -> FilterResult<KeyAction> {
-        let action = app_state
+        let keysym = key.sym;
+        let in_sequence = !app_state.key_sequence.is_empty();
+
+        // Modifiers are pressed along the next key of a sequence
+        if in_sequence && keysym.is_modifier_key() {
+            return FilterResult::Forward;
+        }
+
+        app_state.key_sequence.push((*modifiers, key));
+
+        match app_state
             .config
-            .keybindings
-            .iter()
-            .find_map(|binding| binding.match_action(*modifiers, keysym, mode))
-            .map(Action::into)
-            .map(FilterResult::Intercept);
+            .match_sequence(&app_state.key_sequence, mode)
+        {
+            SequenceMatch::Complete(action) => {
+                Self::end_key_sequence(app_state);
+                FilterResult::Intercept(action.into())
+            }
+            SequenceMatch::Partial => {
+                Self::start_sequence_timer(app_state);
+                FilterResult::Intercept(KeyAction::None)
+            }
+            // An unbound key cancels the sequence without reaching the client
+            SequenceMatch::NoMatch if in_sequence => {
+                Self::end_key_sequence(app_state);
+                FilterResult::Intercept(KeyAction::None)
+            }
+            SequenceMatch::NoMatch => {
+                Self::end_key_sequence(app_state);
+                match keysym.raw() {
+                    KEY_XF86Switch_VT_1..=KEY_XF86Switch_VT_12 => FilterResult::Intercept(
+                        KeyAction::VtSwitch((keysym.raw() - KEY_XF86Switch_VT_1 + 1) as i32),
+                    ),
+                    _ => FilterResult::Forward,
+                }
+            }
+        }
+    }
 
-        match action {
-            None => match keysym.raw() {
-                KEY_XF86Switch_VT_1..=KEY_XF86Switch_VT_12 => FilterResult::Intercept(
-                    KeyAction::VtSwitch((keysym.raw() - KEY_XF86Switch_VT_1 + 1) as i32),
-                ),
-                _ => FilterResult::Forward,
-            },
-            Some(action) => action,
+    /// Forget the sequence if its next key is not pressed before the timeout,
+    /// the timeout restarts with each key of the sequence
+    fn start_sequence_timer(app_state: &mut State) {
+        if let Some(token) = app_state.key_sequence_timer.take() {
+            app_state.loop_handle.remove(token);
+        }
+
+        let timeout = Duration::from_millis(app_state.config.sequence_timeout);
+        app_state.key_sequence_timer = app_state
+            .loop_handle
+            .insert_source(Timer::from_duration(timeout), |_, _, data| {
+                data.state.key_sequence.clear();
+                data.state.key_sequence_timer = None;
+                TimeoutAction::Drop
+            })
+            .map_err(|err| warn!("failed to start the key sequence timer: {err}"))
+            .ok();
+    }
+
+    fn end_key_sequence(app_state: &mut State) {
+        app_state.key_sequence.clear();
+        if let Some(token) = app_state.key_sequence_timer.take() {
+            app_state.loop_handle.remove(token);
         }
     }
 
@@ -213,6 +319,7 @@ impl Wzm {
                     let mut workspace = workspace.borrow_mut();
 
                     workspace.set_focus_matching(&window);
+                    drop(workspace);
 
                     self.state.space.raise_element(&window, true);
                     keyboard.set_focus(
@@ -0,0 +1,412 @@
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use anyhow::Context;
+use smithay::desktop::Window;
+use smithay::reexports::calloop::generic::Generic;
+use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{Logical, Rectangle};
+use tracing::{debug, warn};
+
+use wzm_config::ipc::{
+    self, Event, EventKind, Request, Response, TreeInfo, WindowInfo, WorkspaceInfo,
+};
+
+use crate::shell::Tree;
+use crate::state::app_id_and_title;
+use crate::{State, Wzm};
+
+// A client not reading what is sent to it is dropped once this much is queued,
+// rather than blocking the compositor or buffering forever
+const MAX_PENDING_OUTPUT: usize = 1024 * 1024;
+
+/// Unix socket accepting [`Request`]s from external tools, one JSON object per line
+pub struct IpcServer {
+    pub path: PathBuf,
+    event_loop: LoopHandle<'static, Wzm>,
+    subscribers: Vec<Subscriber>,
+}
+
+struct Subscriber {
+    output: Rc<RefCell<ClientOutput>>,
+    kinds: Vec<EventKind>,
+}
+
+/// Lines queued for a client, written as its non-blocking socket accepts them
+pub(crate) struct ClientOutput {
+    stream: UnixStream,
+    pending: Vec<u8>,
+    // A WRITE source is registered to flush the pending lines
+    flushing: bool,
+    closed: bool,
+}
+
+impl IpcServer {
+    /// Listen on the socket of the given wayland display, see [`ipc::socket_path`]
+    pub fn start(
+        event_loop: &LoopHandle<'static, Wzm>,
+        socket_name: &OsStr,
+    ) -> anyhow::Result<Self> {
+        let path = ipc::socket_path(socket_name).context("XDG_RUNTIME_DIR is not set")?;
+
+        // Left behind by a previous compositor on the same display
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("failed to bind {}", path.display()))?;
+        listener.set_nonblocking(true)?;
+
+        let handle = event_loop.clone();
+        event_loop
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                move |_, listener, _| {
+                    loop {
+                        match listener.as_ref().accept() {
+                            Ok((stream, _)) => {
+                                if let Err(err) = add_client(&handle, stream) {
+                                    warn!("failed to register IPC client: {err:#}");
+                                }
+                            }
+                            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(err) => {
+                                warn!("failed to accept IPC client: {err}");
+                                break;
+                            }
+                        }
+                    }
+
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| err.error)?;
+
+        Ok(Self {
+            path,
+            event_loop: event_loop.clone(),
+            subscribers: vec![],
+        })
+    }
+
+    /// Queue the event for the clients subscribed to its kind, without waiting for them
+    /// to read it. Clients gone or too slow to read their events are dropped.
+    pub fn emit(&mut self, event: &Event) {
+        let kind = event.kind();
+        if !self.subscribers.iter().any(|sub| sub.wants(kind)) {
+            return;
+        }
+
+        let line = match serde_json::to_vec(event) {
+            Ok(line) => line,
+            Err(err) => {
+                warn!("failed to serialize IPC event: {err}");
+                return;
+            }
+        };
+
+        let event_loop = &self.event_loop;
+        self.subscribers.retain(|sub| {
+            if sub.output.borrow().closed {
+                return false;
+            }
+
+            if !sub.wants(kind) {
+                return true;
+            }
+
+            match send_line(event_loop, &sub.output, &line) {
+                Ok(()) => true,
+                Err(err) => {
+                    debug!("dropping IPC subscriber: {err}");
+                    false
+                }
+            }
+        });
+    }
+}
+
+impl Subscriber {
+    fn wants(&self, kind: EventKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl ClientOutput {
+    /// Write the pending lines until the socket would block
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match (&self.stream).write(&self.pending) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.pending.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn add_client(event_loop: &LoopHandle<'static, Wzm>, stream: UnixStream) -> anyhow::Result<()> {
+    stream.set_nonblocking(true)?;
+    let output = Rc::new(RefCell::new(ClientOutput {
+        stream: stream.try_clone()?,
+        pending: vec![],
+        flushing: false,
+        closed: false,
+    }));
+
+    let handle = event_loop.clone();
+    let mut buffer = vec![];
+
+    event_loop
+        .insert_source(
+            Generic::new(stream, Interest::READ, Mode::Level),
+            move |_, stream, wzm| {
+                let mut stream: &UnixStream = stream.as_ref();
+                let mut chunk = [0; 4096];
+                let read = match stream.read(&mut chunk) {
+                    Ok(0) => {
+                        output.borrow_mut().closed = true;
+                        return Ok(PostAction::Remove);
+                    }
+                    Ok(read) => read,
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+                        ) =>
+                    {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) => {
+                        debug!("IPC client disconnected: {err}");
+                        output.borrow_mut().closed = true;
+                        return Ok(PostAction::Remove);
+                    }
+                };
+
+                buffer.extend_from_slice(&chunk[..read]);
+                while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+
+                    let response = match serde_json::from_slice(&line) {
+                        Ok(request) => wzm.handle_ipc_request(request, &output),
+                        Err(err) => Response::Error(format!("invalid request: {err}")),
+                    };
+
+                    let sent = serde_json::to_vec(&response)
+                        .map_err(io::Error::from)
+                        .and_then(|line| send_line(&handle, &output, &line));
+
+                    if let Err(err) = sent {
+                        debug!("failed to answer IPC client: {err}");
+                        output.borrow_mut().closed = true;
+                        return Ok(PostAction::Remove);
+                    }
+                }
+
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|err| err.error)?;
+
+    Ok(())
+}
+
+/// Queue a line for the client and write what its socket accepts right away, the rest is
+/// flushed from a WRITE source once the client reads. Fails when the client is gone or
+/// when its queued output overflows.
+fn send_line(
+    event_loop: &LoopHandle<'static, Wzm>,
+    output: &Rc<RefCell<ClientOutput>>,
+    line: &[u8],
+) -> io::Result<()> {
+    let mut client = output.borrow_mut();
+    if client.closed {
+        return Err(io::ErrorKind::BrokenPipe.into());
+    }
+
+    if client.pending.len() + line.len() + 1 > MAX_PENDING_OUTPUT {
+        client.closed = true;
+        return Err(io::Error::other("the client does not read its output"));
+    }
+
+    client.pending.extend_from_slice(line);
+    client.pending.push(b'\n');
+    if client.flushing {
+        return Ok(());
+    }
+
+    if let Err(err) = client.flush() {
+        client.closed = true;
+        return Err(err);
+    }
+
+    if client.pending.is_empty() {
+        return Ok(());
+    }
+
+    let stream = client.stream.try_clone()?;
+    let output = output.clone();
+    event_loop
+        .insert_source(
+            Generic::new(stream, Interest::WRITE, Mode::Level),
+            move |_, _, _| {
+                let mut client = output.borrow_mut();
+                if let Err(err) = client.flush() {
+                    debug!("IPC client disconnected: {err}");
+                    client.closed = true;
+                    client.pending.clear();
+                }
+
+                if client.closed || client.pending.is_empty() {
+                    client.flushing = false;
+                    return Ok(PostAction::Remove);
+                }
+
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|err| io::Error::other(err.error))?;
+
+    client.flushing = true;
+    Ok(())
+}
+
+impl Wzm {
+    /// Answer a request of the client writing to `output`, subscriptions keep a handle to it
+    pub(crate) fn handle_ipc_request(
+        &mut self,
+        request: Request,
+        output: &Rc<RefCell<ClientOutput>>,
+    ) -> Response {
+        match request {
+            Request::Action(action) => {
+                self.handle_key_action(action.into());
+                Response::Ok
+            }
+            Request::GetWorkspaces => Response::Workspaces(self.state.workspaces_info()),
+            Request::GetFocusedWindow => Response::FocusedWindow(self.state.focused_window_info()),
+            Request::GetTree => Response::Tree(self.state.tree_info()),
+            Request::GetMode => Response::Mode(self.state.current_mode.to_string()),
+            Request::Subscribe(kinds) => {
+                let Some(ipc) = &mut self.state.ipc else {
+                    return Response::Error("the IPC server is not running".to_string());
+                };
+
+                ipc.subscribers.push(Subscriber {
+                    output: output.clone(),
+                    kinds,
+                });
+                Response::Ok
+            }
+        }
+    }
+}
+
+impl State {
+    pub fn workspaces_info(&self) -> Vec<WorkspaceInfo> {
+        let mut workspaces: Vec<_> = self
+            .workspaces
+            .iter()
+            .map(|(num, ws)| WorkspaceInfo {
+                num: *num,
+                focused: *num == self.current_workspace,
+                windows: ws.borrow().leaves().len(),
+            })
+            .collect();
+
+        workspaces.sort_by_key(|ws| ws.num);
+        workspaces
+    }
+
+    pub fn focused_window_info(&self) -> Option<WindowInfo> {
+        let window = self.get_current_workspace().borrow().get_focus()?;
+        Some(self.window_info(&window))
+    }
+
+    /// Describe the window as seen from the workspace holding it
+    pub fn window_info(&self, window: &Window) -> WindowInfo {
+        let geometry = self.space.element_geometry(window).unwrap_or_default();
+        let ws = self
+            .workspaces
+            .values()
+            .find(|ws| ws.borrow().leaves().contains(window))
+            .cloned()
+            .unwrap_or_else(|| self.get_current_workspace());
+
+        let ws = ws.borrow();
+        window_info(&ws, window, geometry)
+    }
+
+    /// Window of any workspace displaying the surface
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<Window> {
+        self.workspaces
+            .values()
+            .flat_map(|ws| ws.borrow().leaves())
+            .find(|window| window.toplevel().is_some_and(|t| t.wl_surface() == surface))
+    }
+
+    /// Queue the event for the IPC subscribers. Handlers can emit events freely,
+    /// a subscriber not reading them never blocks the compositor.
+    pub fn emit_event(&mut self, event: Event) {
+        if let Some(ipc) = &mut self.ipc {
+            ipc.emit(&event);
+        }
+    }
+
+    /// Layout of the current workspace
+    pub fn tree_info(&self) -> TreeInfo {
+        let ws = self.get_current_workspace();
+        let ws = ws.borrow();
+        let root = ws.describe(|window, geometry| window_info(&ws, window, geometry));
+        let floating = ws
+            .floating()
+            .iter()
+            .map(|window| {
+                let geometry = ws.floating_geometry(window).unwrap_or_default();
+                window_info(&ws, window, geometry)
+            })
+            .collect();
+
+        TreeInfo {
+            workspace: self.current_workspace,
+            root,
+            floating,
+        }
+    }
+}
+
+fn window_info(
+    ws: &Tree<Window>,
+    window: &Window,
+    geometry: Rectangle<i32, Logical>,
+) -> WindowInfo {
+    let (app_id, title) = app_id_and_title(window);
+    WindowInfo {
+        app_id,
+        title,
+        geometry: geometry.into(),
+        focused: ws.get_focus().as_ref() == Some(window),
+        floating: ws.is_floating(window),
+        fullscreen: ws.fullscreen_window().as_ref() == Some(window),
+    }
+}
@@ -1,30 +1,27 @@
 #![allow(irrefutable_let_patterns)]
 
-use smithay::desktop::layer_map_for_output;
 pub use smithay::reexports::calloop::EventLoop;
 use smithay::reexports::calloop::LoopSignal;
 pub use smithay::reexports::wayland_server::{Display, DisplayHandle};
-use std::cell::RefCell;
-use std::rc::Rc;
 
 pub use state::State;
-use wzm_config::WzmConfig;
 
 use crate::backend::Backend;
-use crate::shell::{Orientation, Tree};
 
 pub mod action;
 pub mod backend;
+pub mod cursor;
 pub mod decoration;
 pub mod grabs;
 pub mod handlers;
 pub mod input;
+pub mod ipc;
 pub mod renderer;
 pub mod shell;
 pub mod state;
+mod watcher;
 pub struct Wzm {
     pub state: State,
-    pub config: WzmConfig,
     pub backend: Backend,
     pub loop_signal: LoopSignal,
 }
@@ -32,19 +29,23 @@ pub struct Wzm {
 impl Wzm {
     pub fn start_compositor(&mut self) {
         ::std::env::set_var("WAYLAND_DISPLAY", &self.state.socket_name);
+        ::std::env::set_var("XCURSOR_THEME", self.state.cursor.theme_name());
+        ::std::env::set_var("XCURSOR_SIZE", self.state.cursor.size().to_string());
+        if let Some(ipc) = &self.state.ipc {
+            ::std::env::set_var(wzm_config::ipc::SOCKET_ENV, &ipc.path);
+        }
 
-        if let Some(output) = self.state.space.outputs().next() {
-            let map = layer_map_for_output(output);
-            let geometry = map.non_exclusive_zone();
+        if self.state.space.outputs().next().is_none() {
+            panic!("Failed to create Workspace 1 on default Output");
+        }
 
-            self.state.workspaces.insert(
-                0,
-                Rc::new(RefCell::new(Tree::new(geometry, Orientation::Horizontal))),
-            );
-        } else {
-            panic!("Failed to create Workspace 0 on default Output");
+        let outputs: Vec<_> = self.state.space.outputs().cloned().collect();
+        for output in outputs {
+            self.state.add_output_workspace(&output);
         }
 
+        self.mode_changed();
+
         dbg!(&self.state.socket_name);
     }
 }
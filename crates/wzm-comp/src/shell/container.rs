@@ -0,0 +1,225 @@
+use wzm_config::action::Direction;
+
+use crate::shell::node::NodeId;
+use crate::shell::Tree;
+
+impl<T: Clone + Eq> Tree<T> {
+    /// Move the container holding the focused leaf in the given direction, i3 style:
+    /// swap it with a sibling leaf, merge it into an adjacent container,
+    /// or escape into the first ancestor laid out in this direction.
+    pub(crate) fn move_container(&mut self, direction: Direction) {
+        let container = self.focus.0;
+        if container == self.root || self.focus.1.is_none() {
+            return;
+        }
+
+        let Some(target) = self.find_sibling(&container, direction.into()) else {
+            return;
+        };
+
+        let before = matches!(direction, Direction::Left | Direction::Up);
+        let parent = self.parent_of(&container);
+        let target_parent = self.parent_of(&target);
+
+        if target_parent == parent {
+            let parent = self.get_tree(&parent).clone();
+            let mut parent = parent.borrow_mut();
+            match target {
+                NodeId::Leaf(_) => {
+                    let idx = parent.child_index(&container);
+                    let target_idx = parent.child_index(&target);
+                    parent.children.swap(idx, target_idx);
+                }
+                NodeId::Tree(_) => {
+                    drop(parent);
+                    self.detach(&container);
+                    let idx = if before {
+                        self.get_tree(&target).borrow().children.len()
+                    } else {
+                        0
+                    };
+
+                    self.attach(&container, &target, idx);
+                }
+            }
+        } else {
+            // Walk up to the ancestor sitting next to the target and insert the container in between
+            let mut anchor = container;
+            while self.parent_of(&anchor) != target_parent {
+                anchor = self.parent_of(&anchor);
+            }
+
+            let idx = self.get_tree(&target_parent).borrow().child_index(&anchor);
+            let idx = if before { idx } else { idx + 1 };
+            self.detach(&container);
+            self.attach(&container, &target_parent, idx);
+        }
+
+        let root = self.root;
+        self.update_geometries(&root);
+    }
+
+    fn parent_of(&self, id: &NodeId) -> NodeId {
+        self.nodes
+            .get(id)
+            .and_then(|node| node.parent_id())
+            .expect("non root node")
+    }
+
+    // Remove the node from its parent, collapsing the parent if a single child remains
+    fn detach(&mut self, id: &NodeId) {
+        let parent_id = self.parent_of(id);
+        let parent = self.get_tree(&parent_id).clone();
+        let mut parent = parent.borrow_mut();
+        let idx = parent.child_index(id);
+        parent.children.remove(idx);
+        self.reset_ratios(&parent.children);
+
+        if parent_id == self.root || parent.children.len() > 1 {
+            return;
+        }
+
+        let grand_parent_id = parent.parent.expect("non root tree");
+        let grand_parent = self.get_tree(&grand_parent_id).clone();
+        let mut grand_parent = grand_parent.borrow_mut();
+        let idx = grand_parent.child_index(&parent_id);
+
+        match parent.children.pop() {
+            Some(child) => {
+                self.nodes
+                    .get(&child)
+                    .unwrap()
+                    .set_parent_id(&grand_parent_id);
+                grand_parent.children[idx] = child;
+            }
+            None => {
+                grand_parent.children.remove(idx);
+            }
+        }
+
+        self.reset_ratios(&grand_parent.children);
+        drop(parent);
+        self.nodes.remove(&parent_id);
+    }
+
+    fn attach(&mut self, id: &NodeId, parent_id: &NodeId, idx: usize) {
+        let parent = self.get_tree(parent_id).clone();
+        let mut parent = parent.borrow_mut();
+        let idx = idx.min(parent.children.len());
+        parent.children.insert(idx, *id);
+        self.nodes.get(id).unwrap().set_parent_id(parent_id);
+        self.reset_ratios(&parent.children);
+    }
+
+    fn reset_ratios(&self, ids: &[NodeId]) {
+        for id in ids {
+            self.nodes.get(id).unwrap().reset_ratio();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sealed_test::prelude::*;
+    use wzm_config::action::Direction;
+
+    use crate::shell::node::NodeId;
+    use crate::shell::{Orientation, Tree};
+
+    fn children(tree: &Tree<()>, id: NodeId) -> Vec<NodeId> {
+        tree.get_tree(&id).borrow().children.clone()
+    }
+
+    #[sealed_test]
+    fn should_swap_container_with_sibling_leaf() {
+        let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
+
+        tree.insert(());
+        tree.insert(());
+        tree.split_insert((), Orientation::Vertical);
+
+        //      1 H
+        //     / \
+        //    2   4 V
+        //       / \
+        //      3   5
+        let tree1 = NodeId::Tree(1);
+        let leaf2 = NodeId::Leaf(2);
+        let tree4 = NodeId::Tree(4);
+
+        tree.move_container(Direction::Right);
+        assert_eq!(children(&tree, tree1), [leaf2, tree4]);
+
+        tree.move_container(Direction::Left);
+        assert_eq!(children(&tree, tree1), [tree4, leaf2]);
+    }
+
+    #[sealed_test]
+    fn should_escape_container_into_ancestor() {
+        let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
+
+        tree.insert(());
+        tree.insert(());
+        tree.split_insert((), Orientation::Vertical);
+        tree.split_insert((), Orientation::Horizontal);
+
+        //      1 H
+        //     / \
+        //    2   4 V
+        //       / \
+        //      3   6 H
+        //         / \
+        //        5   7
+        let tree1 = NodeId::Tree(1);
+        let leaf2 = NodeId::Leaf(2);
+        let leaf3 = NodeId::Leaf(3);
+        let tree6 = NodeId::Tree(6);
+
+        tree.move_container(Direction::Left);
+
+        //      1 H
+        //    / | \
+        //   2  6  3
+        //     / \
+        //    5   7
+        assert_eq!(children(&tree, tree1), [leaf2, tree6, leaf3]);
+        assert!(!tree.nodes.contains_key(&NodeId::Tree(4)));
+        assert_eq!(tree.parent_of(&leaf3), tree1);
+    }
+
+    #[sealed_test]
+    fn should_merge_container_into_adjacent_container() {
+        let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
+
+        tree.insert(());
+        tree.insert(());
+        tree.set_focus((NodeId::Tree(1), NodeId::Leaf(2)));
+        tree.split_insert((), Orientation::Vertical);
+        tree.set_focus((NodeId::Tree(1), NodeId::Leaf(3)));
+        tree.split_insert((), Orientation::Vertical);
+
+        //      1 H
+        //     / \
+        //    4   6 V
+        //   / \  / \
+        //  2  5 3  7
+        let tree1 = NodeId::Tree(1);
+        let leaf2 = NodeId::Leaf(2);
+        let tree4 = NodeId::Tree(4);
+        let leaf5 = NodeId::Leaf(5);
+        let tree6 = NodeId::Tree(6);
+
+        tree.move_container(Direction::Left);
+
+        //      1 H
+        //      |
+        //      4 V
+        //    / | \
+        //   2  5  6
+        //        / \
+        //       3   7
+        assert_eq!(children(&tree, tree1), [tree4]);
+        assert_eq!(children(&tree, tree4), [leaf2, leaf5, tree6]);
+        assert_eq!(tree.parent_of(&tree6), tree4);
+    }
+}
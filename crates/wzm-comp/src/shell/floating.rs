@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+use smithay::utils::{Logical, Rectangle};
+
+use crate::shell::leaf::Leaf;
+use crate::shell::node::{Node, NodeId};
+use crate::shell::{id, Tree};
+
+impl<T: Clone + Eq> Tree<T> {
+    /// Move the focused leaf from the tree to the floating layer, keeping its geometry,
+    /// or re-insert the focused floating window in the tree after the focused leaf.
+    pub(crate) fn toggle_floating(&mut self) {
+        match self.floating_focus {
+            Some(id) => {
+                let Some(Node::Leaf(leaf)) = self.remove_floating(&id) else {
+                    return;
+                };
+
+                let data = leaf.borrow().data.clone();
+                self.insert(data);
+            }
+            None => {
+                let Some(leaf_id) = self.focus.1 else {
+                    return;
+                };
+
+                let leaf = self.get_leaf(&leaf_id);
+                let (data, geometry) = {
+                    let leaf = leaf.borrow();
+                    (leaf.data.clone(), leaf.geometry)
+                };
+
+                self.remove();
+                self.insert_floating(data, geometry);
+            }
+        }
+    }
+
+    /// Insert a floating leaf on top of the floating stack and focus it
+    pub(crate) fn insert_floating(&mut self, data: T, geometry: Rectangle<i32, Logical>) {
+        let id = NodeId::Leaf(id::next());
+
+        let leaf = Leaf {
+            id,
+            parent: None,
+            geometry,
+            ratio: None,
+            data,
+        };
+
+        self.nodes
+            .insert(id, Node::Leaf(Rc::new(RefCell::new(leaf))));
+        self.floating.push(id);
+        self.floating_focus = Some(id);
+        self.pending_update.push(id);
+    }
+
+    /// Move the tiled leaf holding the given data to the floating layer
+    pub(crate) fn float(&mut self, data: &T, geometry: Rectangle<i32, Logical>) {
+        if self.get_node_for_data(data).is_none() {
+            return;
+        }
+
+        self.set_focus_matching(data);
+
+        // Updates queued for the removed leaf are skipped when draining
+        let pending = mem::take(&mut self.pending_update);
+        self.remove();
+        self.pending_update.extend(pending);
+        self.insert_floating(data.clone(), geometry);
+    }
+
+    /// Focus the floating leaf holding the given data and raise it on top of the stack.
+    /// Returns false if no such floating leaf exists.
+    pub(crate) fn focus_floating(&mut self, data: &T) -> bool {
+        let Some(id) = self.floating_id(data) else {
+            return false;
+        };
+
+        self.floating.retain(|floating| floating != &id);
+        self.floating.push(id);
+        self.floating_focus = Some(id);
+        true
+    }
+
+    pub(crate) fn is_floating(&self, data: &T) -> bool {
+        self.floating_id(data).is_some()
+    }
+
+    /// Returns the floating leaves data, from the bottom to the top of the stack
+    pub(crate) fn floating(&self) -> Vec<T> {
+        self.floating
+            .iter()
+            .map(|id| self.get_leaf(id).borrow().data.clone())
+            .collect()
+    }
+
+    pub(crate) fn floating_geometry(&self, data: &T) -> Option<Rectangle<i32, Logical>> {
+        self.floating_id(data)
+            .map(|id| self.get_leaf(&id).borrow().geometry)
+    }
+
+    pub(crate) fn set_floating_geometry(&mut self, data: &T, geometry: Rectangle<i32, Logical>) {
+        if let Some(id) = self.floating_id(data) {
+            self.get_leaf(&id).borrow_mut().geometry = geometry;
+        }
+    }
+
+    pub(super) fn remove_floating(&mut self, id: &NodeId) -> Option<Node<T>> {
+        self.floating.retain(|floating| floating != id);
+
+        if self.floating_focus == Some(*id) {
+            self.floating_focus = None;
+        }
+
+        self.nodes.remove(id)
+    }
+
+    fn floating_id(&self, data: &T) -> Option<NodeId> {
+        self.floating
+            .iter()
+            .find(|id| &self.get_leaf(id).borrow().data == data)
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sealed_test::prelude::*;
+    use smithay::utils::Rectangle;
+
+    use crate::shell::node::NodeId;
+    use crate::shell::{Orientation, Tree};
+
+    #[sealed_test]
+    fn should_toggle_floating() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert(2);
+        tree.get_pending_updates();
+
+        tree.toggle_floating();
+        let updates = tree.get_pending_updates();
+
+        let root = tree.get_root().borrow().children.clone();
+        assert_eq!(root, [NodeId::Leaf(2)]);
+        assert_eq!(tree.floating(), [2]);
+        assert_eq!(tree.get_focus(), Some(2));
+        assert_eq!(
+            tree.floating_geometry(&2),
+            Some(Rectangle::from_loc_and_size((50, 0), (50, 100)))
+        );
+        assert!(updates.contains(&(1, Rectangle::from_loc_and_size((0, 0), (100, 100)), false)));
+        assert!(updates.contains(&(2, Rectangle::from_loc_and_size((50, 0), (50, 100)), true)));
+
+        tree.toggle_floating();
+        tree.get_pending_updates();
+
+        assert!(tree.floating().is_empty());
+        assert!(!tree.is_floating(&2));
+        assert_eq!(tree.get_root().borrow().children.len(), 2);
+        assert_eq!(tree.get_focus(), Some(2));
+    }
+
+    #[sealed_test]
+    fn should_float_tiled_leaf() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert(2);
+        tree.float(&2, Rectangle::from_loc_and_size((25, 25), (50, 50)));
+        let updates = tree.get_pending_updates();
+
+        assert_eq!(tree.get_root().borrow().children, [NodeId::Leaf(2)]);
+        assert_eq!(tree.floating(), [2]);
+        assert_eq!(tree.get_focus(), Some(2));
+        assert!(updates.contains(&(2, Rectangle::from_loc_and_size((25, 25), (50, 50)), true)));
+        assert!(!updates.contains(&(2, Rectangle::from_loc_and_size((50, 0), (50, 100)), true)));
+    }
+
+    #[sealed_test]
+    fn should_raise_focused_floating() {
+        let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert_floating(2, Rectangle::default());
+        tree.insert_floating(3, Rectangle::default());
+        tree.get_pending_updates();
+
+        assert_eq!(tree.floating(), [2, 3]);
+        assert_eq!(tree.get_focus(), Some(3));
+
+        tree.set_focus_matching(&2);
+        assert_eq!(tree.floating(), [3, 2]);
+        assert_eq!(tree.get_focus(), Some(2));
+
+        tree.set_focus_matching(&1);
+        assert_eq!(tree.get_focus(), Some(1));
+
+        tree.remove();
+        tree.set_focus_matching(&3);
+        tree.remove();
+
+        assert_eq!(tree.floating(), [2]);
+        assert_eq!(tree.get_focus(), None);
+    }
+}
@@ -0,0 +1,162 @@
+use smithay::utils::{Logical, Rectangle};
+
+use crate::shell::node::{Node, NodeId};
+use crate::shell::Tree;
+
+impl<T: Clone + Eq> Tree<T> {
+    /// Toggle fullscreen for the focused window, making it cover the given geometry
+    pub(crate) fn toggle_fullscreen_window(&mut self, geometry: Rectangle<i32, Logical>) {
+        if let Some(id) = self.floating_focus.or(self.focus.1) {
+            self.toggle_fullscreen(id, geometry);
+        }
+    }
+
+    /// Toggle fullscreen for the focused container, making it cover the whole workspace
+    pub(crate) fn toggle_fullscreen_container(&mut self) {
+        let id = self.focus.0;
+        let geometry = self.geometry();
+        self.toggle_fullscreen(id, geometry);
+    }
+
+    /// Leave fullscreen, restoring the tiled and floating layout
+    pub(crate) fn exit_fullscreen(&mut self) {
+        if self.fullscreen.take().is_some() {
+            self.refresh();
+        }
+    }
+
+    /// Returns the fullscreen window, if any. Fullscreen containers are not considered.
+    pub(crate) fn fullscreen_window(&self) -> Option<T> {
+        match self.fullscreen? {
+            (id @ NodeId::Leaf(_), _) => Some(self.get_leaf(&id).borrow().data.clone()),
+            (NodeId::Tree(_), _) => None,
+        }
+    }
+
+    /// Returns the leaves hidden by the current fullscreen window or container
+    pub(crate) fn hidden(&self) -> Vec<T> {
+        let Some((fullscreen, _)) = self.fullscreen else {
+            return vec![];
+        };
+
+        self.nodes
+            .iter()
+            .filter_map(|(id, node)| match node {
+                Node::Leaf(leaf) if !self.is_descendant(id, &fullscreen) => {
+                    Some(leaf.borrow().data.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Drop the fullscreen state if its node was removed from the tree
+    pub(super) fn check_fullscreen(&mut self) {
+        if let Some((id, _)) = self.fullscreen {
+            if !self.nodes.contains_key(&id) {
+                self.fullscreen = None;
+                self.refresh();
+            }
+        }
+    }
+
+    fn toggle_fullscreen(&mut self, id: NodeId, geometry: Rectangle<i32, Logical>) {
+        let previous = self.fullscreen.take();
+        if previous.map(|(previous, _)| previous) != Some(id) {
+            self.fullscreen = Some((id, geometry));
+        }
+
+        self.refresh();
+    }
+
+    fn is_descendant(&self, id: &NodeId, ancestor: &NodeId) -> bool {
+        let mut current = Some(*id);
+        while let Some(id) = current {
+            if &id == ancestor {
+                return true;
+            }
+
+            current = self.nodes.get(&id).and_then(Node::parent_id);
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sealed_test::prelude::*;
+    use smithay::utils::Rectangle;
+
+    use crate::shell::{Orientation, Tree};
+
+    #[sealed_test]
+    fn should_toggle_fullscreen_window() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 30), (100, 70)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert(2);
+        tree.get_pending_updates();
+
+        let output = Rectangle::from_loc_and_size((0, 0), (100, 100));
+        tree.toggle_fullscreen_window(output);
+        let updates = tree.get_pending_updates();
+
+        assert_eq!(tree.fullscreen_window(), Some(2));
+        assert_eq!(tree.hidden(), [1]);
+        assert!(updates.contains(&(2, output, true)));
+
+        tree.toggle_fullscreen_window(output);
+        let updates = tree.get_pending_updates();
+
+        assert_eq!(tree.fullscreen_window(), None);
+        assert!(tree.hidden().is_empty());
+        assert!(updates.contains(&(1, Rectangle::from_loc_and_size((0, 30), (50, 70)), false)));
+        assert!(updates.contains(&(2, Rectangle::from_loc_and_size((50, 30), (50, 70)), true)));
+    }
+
+    #[sealed_test]
+    fn should_toggle_fullscreen_container() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert(2);
+        tree.get_pending_updates();
+        tree.split_insert(3, Orientation::Vertical);
+        tree.get_pending_updates();
+
+        tree.toggle_fullscreen_container();
+        let updates = tree.get_pending_updates();
+
+        assert_eq!(tree.fullscreen_window(), None);
+        assert_eq!(tree.hidden(), [1]);
+        assert!(updates.contains(&(2, Rectangle::from_loc_and_size((0, 0), (100, 50)), false)));
+        assert!(updates.contains(&(3, Rectangle::from_loc_and_size((0, 50), (100, 50)), true)));
+    }
+
+    #[sealed_test]
+    fn should_exit_fullscreen_on_removal() {
+        let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert(2);
+        tree.get_pending_updates();
+
+        tree.toggle_fullscreen_window(Default::default());
+        tree.get_pending_updates();
+        tree.remove();
+        tree.get_pending_updates();
+
+        assert_eq!(tree.fullscreen_window(), None);
+        assert!(tree.hidden().is_empty());
+    }
+}
@@ -0,0 +1,477 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use smithay::utils::{Logical, Rectangle};
+use wzm_config::ipc::{self, NodeInfo, WindowInfo};
+use wzm_config::layout::Layout;
+use wzm_config::matcher::WindowMatcher;
+
+use crate::shell::leaf::Leaf;
+use crate::shell::node::{Node, NodeId};
+use crate::shell::tree::TreeNode;
+use crate::shell::{id, Orientation, Tree};
+
+/// A node of a loaded layout, created once a window fills one of its placeholders
+pub(super) struct Placeholder {
+    // Index of the parent container slot, `None` for the root of the layout
+    parent: Option<usize>,
+    ratio: Option<f32>,
+    kind: PlaceholderKind,
+    node: Option<NodeId>,
+}
+
+enum PlaceholderKind {
+    Container(Orientation),
+    Window(WindowMatcher),
+}
+
+impl<T: Clone + Eq> Tree<T> {
+    /// Describe the tiled layout for IPC clients, leaves are described by `window`
+    /// from their data and geometry. Floating leaves are not part of it.
+    pub(crate) fn describe<F>(&self, window: F) -> NodeInfo
+    where
+        F: Fn(&T, Rectangle<i32, Logical>) -> WindowInfo,
+    {
+        self.describe_node(&self.root, &window)
+    }
+
+    fn describe_node<F>(&self, id: &NodeId, window: &F) -> NodeInfo
+    where
+        F: Fn(&T, Rectangle<i32, Logical>) -> WindowInfo,
+    {
+        match self.nodes.get(id).expect("node should exist") {
+            Node::Leaf(leaf) => {
+                let leaf = leaf.borrow();
+                NodeInfo::Window {
+                    ratio: leaf.ratio,
+                    window: window(&leaf.data, leaf.geometry),
+                }
+            }
+            Node::Tree(tree) => {
+                let tree = tree.borrow();
+                NodeInfo::Container {
+                    orientation: tree.orientation.into(),
+                    geometry: tree.geometry.into(),
+                    ratio: tree.ratio,
+                    children: tree
+                        .children
+                        .iter()
+                        .map(|child| self.describe_node(child, window))
+                        .collect(),
+                }
+            }
+        }
+    }
+}
+
+impl<T: Clone + Eq> Tree<T> {
+    /// Save the tiled layout, leaves are saved with the matcher built from their data
+    pub(crate) fn save_layout<F>(&self, matcher: F) -> Layout
+    where
+        F: Fn(&T) -> WindowMatcher,
+    {
+        self.save_node(&self.root, &matcher)
+    }
+
+    fn save_node<F>(&self, id: &NodeId, matcher: &F) -> Layout
+    where
+        F: Fn(&T) -> WindowMatcher,
+    {
+        match self.nodes.get(id).expect("node should exist") {
+            Node::Leaf(leaf) => {
+                let leaf = leaf.borrow();
+                Layout::Window {
+                    ratio: leaf.ratio,
+                    matcher: matcher(&leaf.data),
+                }
+            }
+            Node::Tree(tree) => {
+                let tree = tree.borrow();
+                Layout::Container {
+                    orientation: tree.orientation.into(),
+                    ratio: tree.ratio,
+                    children: tree
+                        .children
+                        .iter()
+                        .map(|child| self.save_node(child, matcher))
+                        .collect(),
+                }
+            }
+        }
+    }
+
+    /// Append a saved layout to the tree, its windows are placeholders waiting for
+    /// [`Tree::fill_placeholder`]. On an empty tree, the layout root becomes the tree root.
+    pub(crate) fn load_layout(&mut self, layout: Layout) {
+        let first = self.placeholders.len();
+        let root = self.root;
+        let root_taken = self.placeholders.iter().any(|slot| slot.node == Some(root));
+        let root_empty = self.get_root().borrow().children.is_empty();
+        self.flatten_layout(layout, None);
+
+        if let PlaceholderKind::Container(orientation) = self.placeholders[first].kind {
+            if root_empty && !root_taken {
+                self.get_root().borrow_mut().orientation = orientation;
+                self.placeholders[first].node = Some(root);
+            }
+        }
+    }
+
+    fn flatten_layout(&mut self, layout: Layout, parent: Option<usize>) {
+        let slot = self.placeholders.len();
+        match layout {
+            Layout::Container {
+                orientation,
+                ratio,
+                children,
+            } => {
+                self.placeholders.push(Placeholder {
+                    parent,
+                    ratio,
+                    kind: PlaceholderKind::Container(orientation.into()),
+                    node: None,
+                });
+
+                for child in children {
+                    self.flatten_layout(child, Some(slot));
+                }
+            }
+            Layout::Window { ratio, matcher } => self.placeholders.push(Placeholder {
+                parent,
+                ratio,
+                kind: PlaceholderKind::Window(matcher),
+                node: None,
+            }),
+        }
+    }
+
+    /// Put the data in the first empty placeholder `is_match` accepts, creating the
+    /// containers leading to it, and focus it. The data is given back if none matches.
+    pub(crate) fn fill_placeholder<F>(&mut self, data: T, is_match: F) -> Result<(), T>
+    where
+        F: Fn(&WindowMatcher) -> bool,
+    {
+        let Some(slot) = self.find_placeholder(&is_match) else {
+            return Err(data);
+        };
+
+        let parent = self.placeholder_parent(slot);
+        let id = NodeId::Leaf(id::next());
+        let leaf = Leaf {
+            id,
+            parent: Some(parent),
+            geometry: Default::default(),
+            ratio: self.placeholders[slot].ratio,
+            data,
+        };
+
+        self.nodes
+            .insert(id, Node::Leaf(Rc::new(RefCell::new(leaf))));
+        self.attach_placeholder(slot, id, parent);
+
+        let filled = self
+            .placeholders
+            .iter()
+            .all(|slot| slot.node.is_some() || matches!(slot.kind, PlaceholderKind::Container(_)));
+
+        if filled {
+            self.placeholders.clear();
+        }
+
+        self.focus = (parent, Some(id));
+        self.floating_focus = None;
+        let root = self.root;
+        self.update_geometries(&root);
+        Ok(())
+    }
+
+    /// Move the tiled leaf holding the given data to the first empty placeholder
+    /// `is_match` accepts. Returns false if there is no such leaf or placeholder.
+    pub(crate) fn move_to_placeholder<F>(&mut self, data: &T, is_match: F) -> bool
+    where
+        F: Fn(&WindowMatcher) -> bool,
+    {
+        if self.find_placeholder(&is_match).is_none() || self.get_node_for_data(data).is_none() {
+            return false;
+        }
+
+        self.remove_matching(data);
+        self.fill_placeholder(data.clone(), is_match).is_ok()
+    }
+
+    pub(super) fn is_placeholder(&self, id: &NodeId) -> bool {
+        self.placeholders.iter().any(|slot| slot.node == Some(*id))
+    }
+
+    fn find_placeholder<F>(&self, is_match: &F) -> Option<usize>
+    where
+        F: Fn(&WindowMatcher) -> bool,
+    {
+        self.placeholders.iter().position(|slot| {
+            slot.node.is_none()
+                && matches!(&slot.kind, PlaceholderKind::Window(matcher) if is_match(matcher))
+        })
+    }
+
+    // Container of the slot, created along with its missing ancestors.
+    // A container removed once its windows were gone is created again.
+    fn placeholder_parent(&mut self, slot: usize) -> NodeId {
+        let Some(parent) = self.placeholders[slot].parent else {
+            return self.root;
+        };
+
+        if let Some(id) = self.placeholders[parent].node {
+            if self.nodes.contains_key(&id) {
+                return id;
+            }
+        }
+
+        let PlaceholderKind::Container(orientation) = self.placeholders[parent].kind else {
+            unreachable!("window placeholders have no children");
+        };
+
+        let grand_parent = self.placeholder_parent(parent);
+        let id = NodeId::Tree(id::next());
+        let tree = TreeNode {
+            id,
+            parent: Some(grand_parent),
+            children: vec![],
+            geometry: Default::default(),
+            ratio: self.placeholders[parent].ratio,
+            orientation,
+        };
+
+        self.nodes
+            .insert(id, Node::Tree(Rc::new(RefCell::new(tree))));
+        self.attach_placeholder(parent, id, grand_parent);
+        id
+    }
+
+    // Insert the node before the siblings coming after its slot in the layout
+    fn attach_placeholder(&mut self, slot: usize, id: NodeId, parent: NodeId) {
+        self.placeholders[slot].node = Some(id);
+        let tree = self.get_tree(&parent).clone();
+        let mut tree = tree.borrow_mut();
+        let position = tree
+            .children
+            .iter()
+            .position(|child| {
+                self.placeholders
+                    .iter()
+                    .position(|slot| slot.node == Some(*child))
+                    .is_some_and(|other| other > slot)
+            })
+            .unwrap_or(tree.children.len());
+
+        tree.children.insert(position, id);
+    }
+}
+
+impl From<Orientation> for ipc::Orientation {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Vertical => ipc::Orientation::Vertical,
+            Orientation::Horizontal => ipc::Orientation::Horizontal,
+        }
+    }
+}
+
+impl From<ipc::Orientation> for Orientation {
+    fn from(orientation: ipc::Orientation) -> Self {
+        match orientation {
+            ipc::Orientation::Vertical => Orientation::Vertical,
+            ipc::Orientation::Horizontal => Orientation::Horizontal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sealed_test::prelude::*;
+    use smithay::utils::{Logical, Rectangle};
+    use wzm_config::ipc::{self, NodeInfo, WindowInfo};
+    use wzm_config::layout::Layout;
+    use wzm_config::matcher::WindowMatcher;
+
+    use crate::shell::{Orientation, Tree};
+
+    fn window(title: &i32, geometry: Rectangle<i32, Logical>) -> WindowInfo {
+        WindowInfo {
+            app_id: None,
+            title: Some(title.to_string()),
+            geometry: geometry.into(),
+            focused: false,
+            floating: false,
+            fullscreen: false,
+        }
+    }
+
+    #[sealed_test]
+    fn should_describe_nested_containers() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.split_insert(2, Orientation::Vertical);
+        tree.get_pending_updates();
+
+        let NodeInfo::Container {
+            orientation,
+            children,
+            ..
+        } = tree.describe(window)
+        else {
+            panic!("root should be a container");
+        };
+
+        assert_eq!(orientation, ipc::Orientation::Horizontal);
+        assert_eq!(children.len(), 1);
+
+        let NodeInfo::Container {
+            orientation,
+            children,
+            ..
+        } = &children[0]
+        else {
+            panic!("split should create a container");
+        };
+
+        assert_eq!(*orientation, ipc::Orientation::Vertical);
+        let titles: Vec<_> = children
+            .iter()
+            .filter_map(|child| match child {
+                NodeInfo::Window { window, .. } => window.title.clone(),
+                NodeInfo::Container { .. } => None,
+            })
+            .collect();
+
+        assert_eq!(titles, ["1", "2"]);
+    }
+
+    fn placeholder(name: &str, ratio: Option<f32>) -> Layout {
+        Layout::Window {
+            ratio,
+            matcher: WindowMatcher::exact(Some(name), None),
+        }
+    }
+
+    fn fill(tree: &mut Tree<i32>, data: i32) -> Result<(), i32> {
+        tree.fill_placeholder(data, |matcher| {
+            matcher.matches(Some(&data.to_string()), None)
+        })
+    }
+
+    #[sealed_test]
+    fn should_fill_layout_placeholders() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Vertical,
+        );
+
+        let layout = Layout::Container {
+            orientation: ipc::Orientation::Horizontal,
+            ratio: None,
+            children: vec![
+                placeholder("1", Some(0.6)),
+                Layout::Container {
+                    orientation: ipc::Orientation::Vertical,
+                    ratio: None,
+                    children: vec![placeholder("2", None), placeholder("3", None)],
+                },
+            ],
+        };
+
+        tree.load_layout(layout.clone());
+
+        assert_eq!(fill(&mut tree, 3), Ok(()));
+        assert_eq!(fill(&mut tree, 4), Err(4));
+        assert_eq!(fill(&mut tree, 1), Ok(()));
+        assert_eq!(fill(&mut tree, 2), Ok(()));
+        assert_eq!(fill(&mut tree, 1), Err(1));
+        assert!(tree.placeholders.is_empty());
+        tree.get_pending_updates();
+
+        let saved = tree.save_layout(|data| WindowMatcher::exact(Some(&data.to_string()), None));
+        assert_eq!(saved, layout);
+        assert_eq!(tree.get_focus(), Some(2));
+    }
+
+    #[sealed_test]
+    fn should_append_layout_after_existing_windows() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.load_layout(Layout::Container {
+            orientation: ipc::Orientation::Vertical,
+            ratio: None,
+            children: vec![placeholder("2", None), placeholder("3", None)],
+        });
+
+        assert_eq!(fill(&mut tree, 3), Ok(()));
+        tree.get_pending_updates();
+        tree.insert(4);
+        tree.get_pending_updates();
+
+        let NodeInfo::Container { children, .. } = tree.describe(window) else {
+            panic!("root should be a container");
+        };
+
+        assert_eq!(children.len(), 2);
+        let NodeInfo::Container {
+            orientation,
+            children,
+            ..
+        } = &children[1]
+        else {
+            panic!("layout should be appended as a container");
+        };
+
+        assert_eq!(*orientation, ipc::Orientation::Vertical);
+        assert_eq!(children.len(), 2);
+    }
+
+    #[sealed_test]
+    fn should_move_mapped_windows_to_placeholders() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        let layout = Layout::Container {
+            orientation: ipc::Orientation::Horizontal,
+            ratio: None,
+            children: vec![
+                placeholder("1", None),
+                Layout::Container {
+                    orientation: ipc::Orientation::Vertical,
+                    ratio: None,
+                    children: vec![placeholder("2", None), placeholder("3", None)],
+                },
+            ],
+        };
+
+        tree.load_layout(layout.clone());
+
+        // Windows are tiled as usual when mapped, the layout claims them later
+        for data in [3, 2, 1] {
+            tree.insert(data);
+            tree.get_pending_updates();
+            let moved = tree.move_to_placeholder(&data, |matcher| {
+                matcher.matches(Some(&data.to_string()), None)
+            });
+            tree.get_pending_updates();
+            assert!(moved);
+        }
+
+        let saved = tree.save_layout(|data| WindowMatcher::exact(Some(&data.to_string()), None));
+        assert_eq!(saved, layout);
+        assert!(!tree.move_to_placeholder(&1, |_| true));
+    }
+}
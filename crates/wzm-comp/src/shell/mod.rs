@@ -1,9 +1,12 @@
 use std::cell::RefCell;
 use std::collections::BTreeMap;
+use std::mem;
 use std::rc::Rc;
 
 use smithay::utils::{Logical, Rectangle};
+use wzm_config::Gaps;
 
+use layout::Placeholder;
 use leaf::Leaf;
 use tree::TreeNode;
 
@@ -13,14 +16,28 @@ mod leaf;
 mod node;
 mod tree;
 
+mod container;
+mod floating;
+mod fullscreen;
+mod layout;
 mod resize;
 mod siblings;
 
+pub(crate) use siblings::closest_in_direction;
+
 pub struct Tree<T> {
     nodes: BTreeMap<NodeId, Node<T>>,
     root: NodeId,
     focus: (NodeId, Option<NodeId>),
+    // Floating leaves are stored in `nodes` without parent, ordered from bottom to top
+    floating: Vec<NodeId>,
+    floating_focus: Option<NodeId>,
+    // Fullscreen leaf or container, with the geometry it covers
+    fullscreen: Option<(NodeId, Rectangle<i32, Logical>)>,
+    gaps: Gaps,
     pending_update: Vec<NodeId>,
+    // Nodes of loaded layouts, in depth first order, until every window placeholder is filled
+    placeholders: Vec<Placeholder>,
 }
 
 pub enum Direction {
@@ -91,17 +108,36 @@ impl<T: Clone + Eq> Tree<T> {
             nodes,
             root: root_id,
             focus: (root_id, None),
+            floating: vec![],
+            floating_focus: None,
+            fullscreen: None,
+            gaps: Gaps::default(),
             pending_update: vec![],
+            placeholders: vec![],
         }
     }
 
-    pub(crate) fn toggle_layout(&mut self) {
+    pub(crate) fn set_gaps(&mut self, gaps: Gaps) {
+        self.gaps = gaps;
+        self.refresh();
+    }
+
+    /// Invert the orientation of the focused container and return the new one
+    pub(crate) fn toggle_layout(&mut self) -> Orientation {
         let (focused_node, _) = self.focus;
         let node = self.get_tree(&focused_node);
         let mut node = node.borrow_mut();
         node.orientation = node.orientation.invert();
+        let orientation = node.orientation;
         drop(node);
         self.update_geometries(&focused_node);
+        orientation
+    }
+
+    /// Orientation of the focused container
+    pub(crate) fn orientation(&self) -> Orientation {
+        let (focused_node, _) = self.focus;
+        self.get_tree(&focused_node).borrow().orientation
     }
 
     pub(crate) fn move_node(&mut self, target_node_id: NodeId, target_leaf_id: NodeId) {
@@ -135,8 +171,10 @@ impl<T: Clone + Eq> Tree<T> {
     }
 
     pub fn get_pending_updates(&mut self) -> Vec<(T, Rectangle<i32, Logical>, bool)> {
+        self.check_fullscreen();
         let ids: Vec<_> = self.pending_update.drain(..).collect();
-        let focus = self.focus.1;
+        let focus = self.floating_focus.or(self.focus.1);
+        let fullscreen = self.fullscreen;
 
         ids.iter()
             .filter_map(|id| self.nodes.get(id))
@@ -146,7 +184,10 @@ impl<T: Clone + Eq> Tree<T> {
                     let leaf = l.borrow();
                     let activate = focus.map(|id| leaf.id == id).unwrap_or_default();
                     let data = leaf.data.clone();
-                    let geometry = leaf.geometry;
+                    let geometry = match fullscreen {
+                        Some((id, geometry)) if id == leaf.id => geometry,
+                        _ => leaf.geometry,
+                    };
 
                     Some((data, geometry, activate))
                 }
@@ -156,12 +197,17 @@ impl<T: Clone + Eq> Tree<T> {
     }
 
     pub(crate) fn set_focus_matching(&mut self, data: &T) {
+        if self.focus_floating(data) {
+            return;
+        }
+
         let location = self
             .get_node_for_data(data)
             .map(|(tree, leaf)| (tree, Some(leaf)));
 
         if let Some((parent, id)) = location {
-            self.focus = (parent, id)
+            self.focus = (parent, id);
+            self.floating_focus = None;
         }
     }
 
@@ -170,6 +216,10 @@ impl<T: Clone + Eq> Tree<T> {
             self.pending_update.push(leaf);
         }
 
+        if let Some(floating) = self.floating_focus.take() {
+            self.pending_update.push(floating);
+        }
+
         self.focus = (tree, Some(leaf));
         self.pending_update.push(leaf);
     }
@@ -181,12 +231,54 @@ impl<T: Clone + Eq> Tree<T> {
                 Node::Leaf(leaf) => Some(leaf.borrow()),
                 Node::Tree(_) => None,
             })
-            .find(|leaf| &leaf.data == data)
+            .find(|leaf| &leaf.data == data && leaf.parent.is_some())
             .map(|leaf| (leaf.parent.expect("leaf parent"), leaf.id))
     }
 
+    /// Returns the data held by every leaf of the tree
+    pub(crate) fn leaves(&self) -> Vec<T> {
+        self.nodes
+            .values()
+            .filter_map(|node| match node {
+                Node::Leaf(leaf) => Some(leaf.borrow().data.clone()),
+                Node::Tree(_) => None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.nodes.values().any(Node::is_leaf)
+    }
+
+    pub(crate) fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.get_root().borrow().geometry
+    }
+
+    /// Lay the tree out on another area, floating and fullscreen windows move along with it
+    pub(crate) fn set_geometry(&mut self, geometry: Rectangle<i32, Logical>) {
+        let offset = geometry.loc - self.geometry().loc;
+        self.get_root().borrow_mut().geometry = geometry;
+
+        for id in &self.floating {
+            self.get_leaf(id).borrow_mut().geometry.loc += offset;
+        }
+
+        if let Some((_, fullscreen)) = &mut self.fullscreen {
+            fullscreen.loc += offset;
+        }
+
+        self.refresh();
+    }
+
+    /// Recompute every geometry in the tree and mark all nodes for update
+    pub(crate) fn refresh(&mut self) {
+        let root = self.root;
+        self.update_geometries(&root);
+        self.pending_update.extend(self.floating.iter().copied());
+    }
+
     pub(crate) fn get_focus(&self) -> Option<T> {
-        let (_, leaf_id) = self.focus;
+        let leaf_id = self.floating_focus.or(self.focus.1);
         let leaf = self.get_leaf(&leaf_id?);
         Some(leaf.borrow().data.clone())
     }
@@ -228,6 +320,7 @@ impl<T: Clone + Eq> Tree<T> {
 
         drop(tree);
         self.focus.1 = Some(new_leaf_id);
+        self.floating_focus = None;
         let focus = self.focus.0;
         self.update_geometries(&focus);
         debug_assert!(self.focus.1.is_some())
@@ -296,15 +389,59 @@ impl<T: Clone + Eq> Tree<T> {
             .insert(new_leaf_id, Node::Leaf(Rc::new(RefCell::new(new_leaf))));
 
         self.focus = (new_node_id, Some(new_leaf_id));
+        self.floating_focus = None;
         drop(tree);
         self.update_geometries(&tree_id);
         debug_assert!(self.focus.1.is_some())
     }
 
+    /// Remove the tiled or floating leaf holding the given data, wherever the focus is
+    pub(crate) fn remove_matching(&mut self, data: &T) {
+        if self.get_node_for_data(data).is_none() && !self.is_floating(data) {
+            return;
+        }
+
+        self.set_focus_matching(data);
+
+        // Updates queued for the removed leaf are skipped when draining
+        let pending = mem::take(&mut self.pending_update);
+        self.remove();
+        self.pending_update.extend(pending);
+    }
+
+    /// Move the focused window to another workspace, where it keeps floating with its
+    /// geometry or covering the same area if it was fullscreen
+    pub(crate) fn move_focused_to(&mut self, target: &mut Tree<T>) -> Option<T> {
+        let data = self.get_focus()?;
+        let floating = self.floating_geometry(&data);
+        let fullscreen = self
+            .fullscreen
+            .filter(|_| self.fullscreen_window().as_ref() == Some(&data))
+            .map(|(_, geometry)| geometry);
+
+        self.remove();
+
+        match floating {
+            Some(geometry) => target.insert_floating(data.clone(), geometry),
+            None => target.insert(data.clone()),
+        }
+
+        if let Some(geometry) = fullscreen {
+            target.toggle_fullscreen_window(geometry);
+        }
+
+        Some(data)
+    }
+
     /// Remove the focused leaf from the tree, otherwise panic
     pub(crate) fn remove(&mut self) -> Option<Node<T>> {
         #[cfg(not(test))]
         debug_assert!(self.pending_update.is_empty());
+
+        if let Some(id) = self.floating_focus {
+            return self.remove_floating(&id);
+        }
+
         let (tree_id, leaf_id) = self.focus;
         let leaf_id = leaf_id?;
 
@@ -325,6 +462,8 @@ impl<T: Clone + Eq> Tree<T> {
             let empty_tree = tree.children.is_empty() && tree.id != self.root;
             let single_child = tree.children.len() == 1 && tree.id != self.root;
             let no_leaf_in_tree = !parent.has_leaf() && tree.id != self.root;
+            // Containers of a loaded layout keep their shape until it is filled
+            let placeholder = self.is_placeholder(&tree_id);
 
             if empty_tree {
                 let idx = parent.child_index(&tree_id);
@@ -334,7 +473,7 @@ impl<T: Clone + Eq> Tree<T> {
                 drop(tree);
                 self.update_geometries(&parent_id);
                 self.nodes.remove(&tree_id);
-            } else if single_child || no_leaf_in_tree {
+            } else if (single_child || no_leaf_in_tree) && !placeholder {
                 let children: Vec<_> = tree.children.drain(..).collect();
                 for id in &children {
                     self.nodes.get(id).unwrap().set_parent_id(&parent_id)
@@ -438,7 +577,20 @@ impl<T: Clone + Eq> Tree<T> {
             return;
         }
 
-        let mut next_loc = tree.geometry.loc;
+        let (inner, outer) = self.effective_gaps();
+        let area = if tree.id == self.root {
+            let (loc, size) = (tree.geometry.loc, tree.geometry.size);
+            Rectangle::from_loc_and_size(
+                (loc.x + outer, loc.y + outer),
+                (size.w - 2 * outer, size.h - 2 * outer),
+            )
+        } else {
+            tree.geometry
+        };
+
+        // Inner gaps are taken from the space shared by the children
+        let spacing = inner * (tree.children.len() as i32 - 1);
+        let mut next_loc = area.loc;
         let mut default_ratio_count = 0;
         let mut total_non_default_ratio = 0.0;
 
@@ -461,25 +613,30 @@ impl<T: Clone + Eq> Tree<T> {
             let node = self.nodes.get(child).expect("child not found");
             let ratio = node.ratio().unwrap_or(default_ratio);
             let (width, height) = match tree.orientation {
-                Orientation::Vertical => (
-                    tree.geometry.size.w,
-                    (tree.geometry.size.h as f32 * ratio) as i32,
-                ),
-                Orientation::Horizontal => (
-                    (tree.geometry.size.w as f32 * ratio) as i32,
-                    tree.geometry.size.h,
-                ),
+                Orientation::Vertical => {
+                    (area.size.w, ((area.size.h - spacing) as f32 * ratio) as i32)
+                }
+                Orientation::Horizontal => {
+                    (((area.size.w - spacing) as f32 * ratio) as i32, area.size.h)
+                }
             };
 
             let geometry = Rectangle::from_loc_and_size(next_loc, (width, height));
 
             next_loc = match tree.orientation {
-                Orientation::Vertical => (geometry.loc.x, geometry.loc.y + geometry.size.h).into(),
+                Orientation::Vertical => {
+                    (geometry.loc.x, geometry.loc.y + geometry.size.h + inner).into()
+                }
                 Orientation::Horizontal => {
-                    (geometry.loc.x + geometry.size.w, geometry.loc.y).into()
+                    (geometry.loc.x + geometry.size.w + inner, geometry.loc.y).into()
                 }
             };
-            node.set_geometry(geometry);
+            match self.fullscreen {
+                Some((id, fullscreen)) if id == *child && !node.is_leaf() => {
+                    node.set_geometry(fullscreen)
+                }
+                _ => node.set_geometry(geometry),
+            }
 
             if let Node::Tree(_) = node {
                 self.update_geometries(child);
@@ -487,6 +644,21 @@ impl<T: Clone + Eq> Tree<T> {
         }
     }
 
+    // Returns the inner and outer gaps, smart gaps are disabled with a single tiled leaf
+    fn effective_gaps(&self) -> (i32, i32) {
+        let tiled = self
+            .nodes
+            .values()
+            .filter(|node| node.is_leaf() && node.parent_id().is_some())
+            .count();
+
+        if self.gaps.smart && tiled <= 1 {
+            (0, 0)
+        } else {
+            (self.gaps.inner as i32, self.gaps.outer as i32)
+        }
+    }
+
     fn descendant_leaf(&self, node_id: &NodeId, direction: Direction) -> Option<NodeId> {
         debug_assert!(matches!(node_id, NodeId::Tree(_)));
         let tree = self.get_tree(node_id);
@@ -537,6 +709,8 @@ mod test {
     use sealed_test::prelude::*;
     use smithay::utils::Rectangle;
 
+    use wzm_config::Gaps;
+
     use crate::shell::node::NodeId;
     use crate::shell::{Direction, Orientation, Tree};
 
@@ -773,6 +947,123 @@ mod test {
         );
     }
 
+    #[sealed_test]
+    fn should_update_geometries_with_gaps() {
+        //   1
+        //  / \
+        // 2   4
+        //   /   \
+        //  3     5
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 200)),
+            Orientation::Horizontal,
+        );
+
+        tree.set_gaps(Gaps {
+            inner: 10,
+            outer: 5,
+            smart: false,
+        });
+
+        tree.insert(());
+        tree.get_pending_updates();
+
+        let leaf = tree.nodes.get(&NodeId::Leaf(2)).unwrap();
+        assert_eq!(
+            leaf.geometry(),
+            Rectangle::from_loc_and_size((5, 5), (90, 190))
+        );
+
+        tree.insert(());
+        tree.get_pending_updates();
+        tree.split_insert((), Orientation::Vertical);
+        tree.get_pending_updates();
+
+        let leaf2 = tree.nodes.get(&NodeId::Leaf(2)).unwrap();
+        let leaf3 = tree.nodes.get(&NodeId::Leaf(3)).unwrap();
+        let node4 = tree.nodes.get(&NodeId::Tree(4)).unwrap();
+        let leaf5 = tree.nodes.get(&NodeId::Leaf(5)).unwrap();
+        assert_eq!(
+            leaf2.geometry(),
+            Rectangle::from_loc_and_size((5, 5), (40, 190))
+        );
+        assert_eq!(
+            node4.geometry(),
+            Rectangle::from_loc_and_size((55, 5), (40, 190))
+        );
+        assert_eq!(
+            leaf3.geometry(),
+            Rectangle::from_loc_and_size((55, 5), (40, 90))
+        );
+        assert_eq!(
+            leaf5.geometry(),
+            Rectangle::from_loc_and_size((55, 105), (40, 90))
+        );
+    }
+
+    #[sealed_test]
+    fn should_disable_smart_gaps_with_single_window() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 200)),
+            Orientation::Horizontal,
+        );
+
+        tree.set_gaps(Gaps {
+            inner: 10,
+            outer: 5,
+            smart: true,
+        });
+
+        tree.insert(());
+        tree.get_pending_updates();
+
+        let leaf = tree.nodes.get(&NodeId::Leaf(2)).unwrap();
+        assert_eq!(
+            leaf.geometry(),
+            Rectangle::from_loc_and_size((0, 0), (100, 200))
+        );
+
+        tree.insert(());
+        tree.get_pending_updates();
+
+        let leaf2 = tree.nodes.get(&NodeId::Leaf(2)).unwrap();
+        let leaf3 = tree.nodes.get(&NodeId::Leaf(3)).unwrap();
+        assert_eq!(
+            leaf2.geometry(),
+            Rectangle::from_loc_and_size((5, 5), (40, 190))
+        );
+        assert_eq!(
+            leaf3.geometry(),
+            Rectangle::from_loc_and_size((55, 5), (40, 190))
+        );
+
+        tree.remove();
+        tree.get_pending_updates();
+
+        let leaf2 = tree.nodes.get(&NodeId::Leaf(2)).unwrap();
+        assert_eq!(
+            leaf2.geometry(),
+            Rectangle::from_loc_and_size((0, 0), (100, 200))
+        );
+    }
+
+    #[sealed_test]
+    fn should_list_leaves() {
+        let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
+        assert!(tree.is_empty());
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.split_insert(2, Orientation::Vertical);
+        tree.get_pending_updates();
+
+        assert!(!tree.is_empty());
+        assert_eq!(tree.leaves(), [1, 2]);
+
+        tree.refresh();
+        assert_eq!(tree.get_pending_updates().len(), 2);
+    }
+
     #[sealed_test]
     fn should_update_focus_on_removal() {
         let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
@@ -798,4 +1089,66 @@ mod test {
         tree.remove();
         tree.get_pending_updates();
     }
+
+    #[sealed_test]
+    fn should_move_floating_window_to_workspace() {
+        let geometry = Rectangle::from_loc_and_size((0, 0), (100, 100));
+        let mut tree = Tree::new(geometry, Orientation::Horizontal);
+        let mut target = Tree::new(geometry, Orientation::Horizontal);
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert_floating(2, Rectangle::from_loc_and_size((10, 10), (40, 30)));
+        tree.get_pending_updates();
+
+        assert_eq!(tree.move_focused_to(&mut target), Some(2));
+        let updates = target.get_pending_updates();
+
+        assert!(!tree.is_floating(&2));
+        assert_eq!(tree.leaves(), [1]);
+        assert_eq!(target.floating(), [2]);
+        assert_eq!(target.get_focus(), Some(2));
+        assert!(updates.contains(&(2, Rectangle::from_loc_and_size((10, 10), (40, 30)), true)));
+    }
+
+    #[sealed_test]
+    fn should_move_fullscreen_window_to_workspace() {
+        let geometry = Rectangle::from_loc_and_size((0, 30), (100, 70));
+        let output = Rectangle::from_loc_and_size((0, 0), (100, 100));
+        let mut tree = Tree::new(geometry, Orientation::Horizontal);
+        let mut target = Tree::new(geometry, Orientation::Horizontal);
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert(2);
+        tree.get_pending_updates();
+        tree.toggle_fullscreen_window(output);
+        tree.get_pending_updates();
+
+        assert_eq!(tree.move_focused_to(&mut target), Some(2));
+        tree.get_pending_updates();
+        let updates = target.get_pending_updates();
+
+        assert_eq!(tree.fullscreen_window(), None);
+        assert_eq!(target.fullscreen_window(), Some(2));
+        assert!(updates.contains(&(2, output, true)));
+    }
+
+    #[sealed_test]
+    fn should_move_floating_windows_with_the_tree() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.insert_floating(2, Rectangle::from_loc_and_size((10, 10), (40, 30)));
+        tree.get_pending_updates();
+
+        tree.set_geometry(Rectangle::from_loc_and_size((100, 0), (200, 100)));
+        let updates = tree.get_pending_updates();
+
+        assert!(updates.contains(&(1, Rectangle::from_loc_and_size((100, 0), (200, 100)), false)));
+        assert!(updates.contains(&(2, Rectangle::from_loc_and_size((110, 10), (40, 30)), true)));
+    }
 }
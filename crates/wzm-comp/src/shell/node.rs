@@ -58,6 +58,13 @@ impl<T> Node<T> {
         }
     }
 
+    pub fn reset_ratio(&self) {
+        match self {
+            Node::Leaf(leaf) => leaf.borrow_mut().ratio = None,
+            Node::Tree(tree) => tree.borrow_mut().ratio = None,
+        }
+    }
+
     pub fn parent_id(&self) -> Option<NodeId> {
         match self {
             Node::Leaf(leaf) => leaf.borrow().parent,
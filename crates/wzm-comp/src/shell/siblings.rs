@@ -1,4 +1,7 @@
-use crate::shell::node::NodeId;
+use smithay::utils::{Logical, Rectangle};
+use wzm_config::action::Direction;
+
+use crate::shell::node::{Node, NodeId};
 use crate::shell::{Orientation, Tree};
 
 pub(super) enum SiblingDirection {
@@ -8,7 +11,119 @@ pub(super) enum SiblingDirection {
     Down,
 }
 
+impl From<Direction> for SiblingDirection {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Left => SiblingDirection::Left,
+            Direction::Right => SiblingDirection::Right,
+            Direction::Up => SiblingDirection::Up,
+            Direction::Down => SiblingDirection::Down,
+        }
+    }
+}
+
 impl<T: Clone + Eq> Tree<T> {
+    /// Focus the closest window in the given direction, among the tiled leaves when a tiled
+    /// leaf is focused and among the floating windows when a floating one is.
+    /// Returns false if there is no window in that direction.
+    pub(crate) fn focus_in_direction(&mut self, direction: Direction) -> bool {
+        if self.floating_focus.is_some() {
+            return match self.floating_in_direction(direction) {
+                Some(data) => self.focus_floating(&data),
+                None => false,
+            };
+        }
+
+        match self.leaf_in_direction(direction) {
+            Some(focus) => {
+                self.set_focus(focus);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Find the closest floating window from the focused floating one in the given direction,
+    /// only windows whose center is past the focused window center are considered
+    fn floating_in_direction(&self, direction: Direction) -> Option<T> {
+        let focus = self.floating_focus?;
+        let origin = self.get_leaf(&focus).borrow().geometry;
+
+        let candidates = self.floating.iter().filter(|id| **id != focus).map(|id| {
+            let leaf = self.get_leaf(id).borrow();
+            (leaf.data.clone(), leaf.geometry)
+        });
+
+        closest_in_direction(direction, &origin, candidates)
+    }
+
+    /// Focus the visible leaf closest to an area outside the tree, coming from it in the
+    /// given direction, such as the window focused on the neighbouring output.
+    /// Returns false if no leaf is visible.
+    pub(crate) fn focus_closest(
+        &mut self,
+        direction: Direction,
+        origin: &Rectangle<i32, Logical>,
+    ) -> bool {
+        let hidden = self.hidden();
+        let candidates = self.nodes.iter().filter_map(|(id, node)| match node {
+            Node::Leaf(leaf) => {
+                let leaf = leaf.borrow();
+                (!hidden.contains(&leaf.data)).then_some((*id, leaf.geometry))
+            }
+            Node::Tree(_) => None,
+        });
+
+        let Some(id) = closest_in_direction(direction, origin, candidates) else {
+            return false;
+        };
+
+        if self.floating.contains(&id) {
+            let data = self.get_leaf(&id).borrow().data.clone();
+            return self.focus_floating(&data);
+        }
+
+        let Some(parent) = self.get_leaf(&id).borrow().parent else {
+            return false;
+        };
+
+        self.set_focus((parent, id));
+        true
+    }
+
+    /// Find the closest leaf from the focused one in the given direction.
+    /// Returns the leaf parent and the leaf.
+    pub(crate) fn leaf_in_direction(&self, direction: Direction) -> Option<(NodeId, NodeId)> {
+        let focus = self.focus.1?;
+        let sibling = self.find_sibling(&focus, direction.into())?;
+        let origin = self.get_leaf(&focus).borrow().geometry;
+
+        // Pick the nearest leaf, then the one sharing the longest edge with the focused leaf
+        let leaf = self.leaves_under(&sibling).into_iter().min_by_key(|id| {
+            let geometry = self.get_leaf(id).borrow().geometry;
+            (
+                distance(direction, &origin, &geometry),
+                -overlap(direction, &origin, &geometry),
+            )
+        })?;
+
+        let parent = self.get_leaf(&leaf).borrow().parent?;
+        Some((parent, leaf))
+    }
+
+    fn leaves_under(&self, node_id: &NodeId) -> Vec<NodeId> {
+        match node_id {
+            NodeId::Leaf(_) => vec![*node_id],
+            NodeId::Tree(_) => {
+                let tree = self.get_tree(node_id).borrow();
+                tree.children
+                    .iter()
+                    .flat_map(|id| self.leaves_under(id))
+                    .collect()
+            }
+        }
+    }
+
     pub(super) fn find_sibling(
         &self,
         node_id: &NodeId,
@@ -62,12 +177,83 @@ impl<T: Clone + Eq> Tree<T> {
     }
 }
 
+/// Pick the nearest candidate from the origin in the given direction, then the one sharing
+/// the longest edge with it. Only candidates whose center is past the origin center count.
+pub(crate) fn closest_in_direction<T>(
+    direction: Direction,
+    origin: &Rectangle<i32, Logical>,
+    candidates: impl Iterator<Item = (T, Rectangle<i32, Logical>)>,
+) -> Option<T> {
+    candidates
+        .filter(|(_, geometry)| is_towards(direction, origin, geometry))
+        .min_by_key(|(_, geometry)| {
+            (
+                distance(direction, origin, geometry),
+                -overlap(direction, origin, geometry),
+            )
+        })
+        .map(|(data, _)| data)
+}
+
+fn distance(
+    direction: Direction,
+    origin: &Rectangle<i32, Logical>,
+    target: &Rectangle<i32, Logical>,
+) -> i32 {
+    let distance = match direction {
+        Direction::Left => origin.loc.x - (target.loc.x + target.size.w),
+        Direction::Right => target.loc.x - (origin.loc.x + origin.size.w),
+        Direction::Up => origin.loc.y - (target.loc.y + target.size.h),
+        Direction::Down => target.loc.y - (origin.loc.y + origin.size.h),
+    };
+
+    distance.abs()
+}
+
+fn is_towards(
+    direction: Direction,
+    origin: &Rectangle<i32, Logical>,
+    target: &Rectangle<i32, Logical>,
+) -> bool {
+    // Centers are compared doubled to stay in integers
+    let center = |rect: &Rectangle<i32, Logical>| {
+        (2 * rect.loc.x + rect.size.w, 2 * rect.loc.y + rect.size.h)
+    };
+
+    let (origin, target) = (center(origin), center(target));
+    match direction {
+        Direction::Left => target.0 < origin.0,
+        Direction::Right => target.0 > origin.0,
+        Direction::Up => target.1 < origin.1,
+        Direction::Down => target.1 > origin.1,
+    }
+}
+
+fn overlap(
+    direction: Direction,
+    origin: &Rectangle<i32, Logical>,
+    target: &Rectangle<i32, Logical>,
+) -> i32 {
+    match direction {
+        Direction::Left | Direction::Right => {
+            (origin.loc.y + origin.size.h).min(target.loc.y + target.size.h)
+                - origin.loc.y.max(target.loc.y)
+        }
+        Direction::Up | Direction::Down => {
+            (origin.loc.x + origin.size.w).min(target.loc.x + target.size.w)
+                - origin.loc.x.max(target.loc.x)
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::shell::node::NodeId;
     use crate::shell::siblings::SiblingDirection;
     use crate::shell::{Orientation, Tree};
     use sealed_test::prelude::*;
+    use smithay::utils::Rectangle;
+    use wzm_config::action::Direction;
 
     #[sealed_test]
     fn get_siblings() {
@@ -137,6 +323,71 @@ mod test {
         );
     }
 
+    #[sealed_test]
+    fn should_find_leaf_in_direction() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (100, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(());
+        tree.insert(());
+        tree.split_insert((), Orientation::Vertical);
+        tree.set_focus((NodeId::Tree(1), NodeId::Leaf(2)));
+        tree.split_insert((), Orientation::Vertical);
+        tree.set_focus((NodeId::Tree(6), NodeId::Leaf(7)));
+        tree.split_insert((), Orientation::Horizontal);
+
+        //      1
+        //     / \
+        //    /   \
+        //   6     4
+        //  / \   / \
+        // 2   8 3   5
+        //    / \
+        //   7   9
+        let tree4 = NodeId::Tree(4);
+        let leaf2 = NodeId::Leaf(2);
+        let leaf3 = NodeId::Leaf(3);
+        let leaf5 = NodeId::Leaf(5);
+        let tree6 = NodeId::Tree(6);
+        let leaf7 = NodeId::Leaf(7);
+        let tree8 = NodeId::Tree(8);
+        let leaf9 = NodeId::Leaf(9);
+
+        tree.set_focus((tree8, leaf9));
+        assert_eq!(
+            tree.leaf_in_direction(Direction::Right),
+            Some((tree4, leaf5))
+        );
+        assert_eq!(
+            tree.leaf_in_direction(Direction::Left),
+            Some((tree8, leaf7))
+        );
+        assert_eq!(tree.leaf_in_direction(Direction::Up), Some((tree6, leaf2)));
+        assert_eq!(tree.leaf_in_direction(Direction::Down), None);
+
+        tree.set_focus((tree4, leaf5));
+        assert_eq!(
+            tree.leaf_in_direction(Direction::Left),
+            Some((tree8, leaf9))
+        );
+        assert_eq!(tree.leaf_in_direction(Direction::Up), Some((tree4, leaf3)));
+
+        tree.set_focus((tree4, leaf3));
+        assert_eq!(
+            tree.leaf_in_direction(Direction::Left),
+            Some((tree6, leaf2))
+        );
+
+        tree.set_focus((tree6, leaf2));
+        assert_eq!(
+            tree.leaf_in_direction(Direction::Down),
+            Some((tree8, leaf7))
+        );
+        assert_eq!(tree.leaf_in_direction(Direction::Up), None);
+    }
+
     #[sealed_test]
     fn get_first_parent_with_inverted_orientation() {
         let mut tree = Tree::new(Default::default(), Orientation::Horizontal);
@@ -163,4 +414,59 @@ mod test {
         assert_eq!(ancestor, tree4);
         assert_eq!(horizontal_parent, Some(tree1));
     }
+
+    #[sealed_test]
+    fn should_move_focus_between_floating_windows() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((0, 0), (1000, 1000)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.insert_floating(2, Rectangle::from_loc_and_size((600, 100), (200, 200)));
+        tree.insert_floating(3, Rectangle::from_loc_and_size((50, 500), (200, 200)));
+        tree.insert_floating(4, Rectangle::from_loc_and_size((100, 100), (200, 200)));
+        tree.get_pending_updates();
+
+        assert!(tree.focus_in_direction(Direction::Right));
+        assert_eq!(tree.get_focus(), Some(2));
+
+        assert!(tree.focus_in_direction(Direction::Down));
+        assert_eq!(tree.get_focus(), Some(3));
+
+        assert!(!tree.focus_in_direction(Direction::Down));
+        assert_eq!(tree.get_focus(), Some(3));
+        assert_eq!(tree.floating(), [4, 2, 3]);
+    }
+
+    #[sealed_test]
+    fn should_focus_closest_leaf_from_another_output() {
+        let mut tree = Tree::new(
+            Rectangle::from_loc_and_size((100, 0), (200, 100)),
+            Orientation::Horizontal,
+        );
+
+        tree.insert(1);
+        tree.get_pending_updates();
+        tree.insert(2);
+        tree.get_pending_updates();
+
+        let left_output = Rectangle::from_loc_and_size((0, 0), (100, 100));
+        assert!(tree.focus_closest(Direction::Right, &left_output));
+        assert_eq!(tree.get_focus(), Some(1));
+
+        let right_output = Rectangle::from_loc_and_size((300, 0), (100, 100));
+        assert!(tree.focus_closest(Direction::Left, &right_output));
+        assert_eq!(tree.get_focus(), Some(2));
+
+        let mut floating = Tree::new(right_output, Orientation::Horizontal);
+        floating.insert_floating(3, Rectangle::from_loc_and_size((350, 10), (40, 40)));
+        floating.insert_floating(4, Rectangle::from_loc_and_size((310, 50), (20, 20)));
+        floating.get_pending_updates();
+        assert!(floating.focus_closest(Direction::Right, &left_output));
+        assert_eq!(floating.get_focus(), Some(4));
+
+        let mut empty: Tree<i32> = Tree::new(right_output, Orientation::Horizontal);
+        assert!(!empty.focus_closest(Direction::Right, &left_output));
+    }
 }
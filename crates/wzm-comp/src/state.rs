@@ -1,38 +1,56 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::OsString;
+use std::mem;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 
-use smithay::desktop::{PopupManager, Space, Window, WindowSurfaceType};
+use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
+use smithay::backend::renderer::element::{AsRenderElements, Element, Wrap};
+use smithay::backend::renderer::{ImportAll, ImportMem, Renderer};
+use smithay::desktop::space::{space_render_elements, SpaceRenderElements};
+use smithay::desktop::{
+    layer_map_for_output, LayerSurface, PopupManager, Space, Window, WindowSurfaceType,
+};
+use smithay::input::keyboard::ModifiersState;
 use smithay::input::{Seat, SeatState};
 use smithay::output::Output;
 use smithay::reexports::calloop::generic::Generic;
-use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction, RegistrationToken};
+use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode;
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
 use smithay::reexports::wayland_server::backend::{ClientData, ClientId, DisconnectReason};
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::{Display, DisplayHandle};
-use smithay::utils::{Logical, Point};
-use smithay::wayland::compositor::{CompositorClientState, CompositorState};
+use smithay::utils::{IsAlive, Logical, Point, Rectangle, Scale, Size};
+use smithay::wayland::compositor::{with_states, CompositorClientState, CompositorState};
 use smithay::wayland::output::OutputManagerState;
 use smithay::wayland::selection::data_device::DataDeviceState;
-use smithay::wayland::shell::wlr_layer::WlrLayerShellState;
+use smithay::wayland::shell::wlr_layer::{Layer, WlrLayerShellState};
 use smithay::wayland::shell::xdg::decoration::XdgDecorationState;
-use smithay::wayland::shell::xdg::XdgShellState;
+use smithay::wayland::shell::xdg::{SurfaceCachedState, XdgShellState, XdgToplevelSurfaceData};
 use smithay::wayland::shm::ShmState;
 use smithay::wayland::socket::ListeningSocketSource;
 use smithay::wayland::xdg_activation::XdgActivationState;
 use smithay::wayland::xdg_foreign::XdgForeignState;
+use tracing::warn;
 
+use wzm_config::action::KeyAction;
+use wzm_config::ipc::Event;
+use wzm_config::window_rule::window_rule;
 use wzm_config::{keybinding, WzmConfig};
 
+use crate::cursor::{Cursor, PointerRenderElement};
+use crate::ipc::IpcServer;
 use crate::shell::{Orientation, Tree};
-use crate::Wzm;
+use crate::{watcher, Wzm};
 
 pub struct State {
     pub start_time: std::time::Instant,
     pub socket_name: OsString,
     pub display_handle: DisplayHandle,
+    pub loop_handle: LoopHandle<'static, Wzm>,
     pub space: Space<Window>,
     // Smithay State
     pub compositor_state: CompositorState,
@@ -47,19 +65,41 @@ pub struct State {
     pub xdg_foreign_state: XdgForeignState,
     pub layer_shell_state: WlrLayerShellState,
     pub seat: Seat<Wzm>,
+    pub cursor: Cursor,
+    pub ipc: Option<IpcServer>,
     // We should use this in calloopdata, not wazm
     pub config: WzmConfig,
+    pub config_path: PathBuf,
 
     // Shell
     pub mod_pressed: bool,
     pub current_mode: keybinding::Mode,
+    // Modes to return to on `ExitMode`, the most recent last
+    pub previous_modes: Vec<keybinding::Mode>,
+    // Keys of the sequence being typed, forgotten by the timer if the next one takes too long
+    pub key_sequence: Vec<(ModifiersState, keybinding::Key)>,
+    pub key_sequence_timer: Option<RegistrationToken>,
+    // Keycode of the last key pressed, release bindings only fire if it is the released one
+    pub last_pressed_key: Option<u32>,
+    pub released_action: Option<KeyAction>,
     pub workspaces: HashMap<u8, Rc<RefCell<Tree<Window>>>>,
     pub current_workspace: u8,
+    // Output each workspace is laid out on and the workspace shown on each output,
+    // the current workspace is one of them
+    pub workspace_outputs: HashMap<u8, Output>,
+    pub shown_workspaces: Vec<(Output, u8)>,
     pub next_layout: Option<Orientation>,
+    // Top layer surfaces unmapped while a window is fullscreen
+    pub hidden_layers: Vec<(Output, LayerSurface)>,
 }
 
 impl State {
-    pub fn new(event_loop: LoopHandle<Wzm>, display: Display<Wzm>, output: &Output) -> Self {
+    pub fn new(
+        event_loop: LoopHandle<'static, Wzm>,
+        display: Display<Wzm>,
+        seat_name: String,
+        config_path: PathBuf,
+    ) -> Self {
         let start_time = std::time::Instant::now();
 
         let dh = display.handle();
@@ -80,11 +120,29 @@ impl State {
 
         // A seat is a group of keyboards, pointer and touch devices.
         // A seat typically has a pointer and maintains a keyboard focus and a pointer focus.
-        let mut seat: Seat<Wzm> = seat_state.new_wl_seat(&dh, "winit");
+        let mut seat: Seat<Wzm> = seat_state.new_wl_seat(&dh, seat_name);
+
+        let config = WzmConfig::load(&config_path).unwrap_or_else(|err| {
+            warn!("Failed to load config, using the default one: {err:#}");
+            WzmConfig::default()
+        });
 
         // Notify clients that we have a keyboard, for the sake of the example we assume that keyboard is always present.
         // You may want to track keyboard hot-plug in real compositor.
-        seat.add_keyboard(Default::default(), 200, 25).unwrap();
+        let keyboard = &config.keyboard;
+        if let Err(err) = seat.add_keyboard(
+            keyboard.xkb_config(),
+            keyboard.repeat_delay,
+            keyboard.repeat_rate,
+        ) {
+            warn!("Invalid keyboard config, using the default keymap: {err:?}");
+            seat.add_keyboard(
+                Default::default(),
+                keyboard.repeat_delay,
+                keyboard.repeat_rate,
+            )
+            .unwrap();
+        }
 
         // Notify clients that we have a pointer (mouse)
         // Here we assume that there is always pointer plugged in
@@ -94,13 +152,20 @@ impl State {
         //
         // Windows get a position and stacking order through mapping.
         // Outputs become views of a part of the Space and can be rendered via Space::render_output.
-        let mut space = Space::default();
-        let socket_name = Self::init_wayland_listener(display, event_loop);
-        space.map_output(output, (0, 0));
+        // Outputs are mapped by the backend.
+        let space = Space::default();
+        let cursor = Cursor::load(&config.cursor);
+
+        watcher::init_config_watcher(&event_loop, &config_path, &config.sources);
+        let socket_name = Self::init_wayland_listener(display, event_loop.clone());
+        let ipc = IpcServer::start(&event_loop, &socket_name)
+            .map_err(|err| warn!("Failed to start the IPC server: {err:#}"))
+            .ok();
 
         Self {
             start_time,
             display_handle: dh,
+            loop_handle: event_loop,
             space,
             socket_name,
             compositor_state,
@@ -115,12 +180,24 @@ impl State {
             xdg_foreign_state,
             layer_shell_state,
             seat,
-            config: WzmConfig::get().expect("failed to get config"),
+            cursor,
+            ipc,
+            config,
+            config_path,
             mod_pressed: false,
             current_mode: Default::default(),
+            previous_modes: vec![],
+            key_sequence: vec![],
+            key_sequence_timer: None,
+            last_pressed_key: None,
+            released_action: None,
             workspaces: Default::default(),
-            current_workspace: 0,
+            // Numbered from 1 as in i3 and sway, like the workspace keybindings
+            current_workspace: 1,
+            workspace_outputs: Default::default(),
+            shown_workspaces: vec![],
             next_layout: None,
+            hidden_layers: vec![],
         }
     }
 
@@ -175,6 +252,123 @@ impl State {
             })
     }
 
+    /// Keep the pointer on the outputs, a motion leaving them is clamped
+    /// to the output the pointer was on
+    pub fn clamp_to_outputs(
+        &self,
+        from: Point<f64, Logical>,
+        to: Point<f64, Logical>,
+    ) -> Point<f64, Logical> {
+        let geometries: Vec<_> = self
+            .space
+            .outputs()
+            .filter_map(|output| self.space.output_geometry(output))
+            .collect();
+
+        if geometries.iter().any(|geo| geo.to_f64().contains(to)) {
+            return to;
+        }
+
+        let Some(geo) = geometries
+            .iter()
+            .find(|geo| geo.to_f64().contains(from))
+            .or(geometries.first())
+        else {
+            return from;
+        };
+
+        let geo = geo.to_f64();
+        Point::from((
+            to.x.clamp(geo.loc.x, geo.loc.x + geo.size.w - 1.0),
+            to.y.clamp(geo.loc.y, geo.loc.y + geo.size.h - 1.0),
+        ))
+    }
+
+    /// Cursor elements for the output, in output coordinates.
+    /// The cursor is only drawn on the output under the pointer.
+    pub fn cursor_elements<R>(
+        &mut self,
+        renderer: &mut R,
+        output: &Output,
+    ) -> Vec<PointerRenderElement<R>>
+    where
+        R: Renderer + ImportAll + ImportMem,
+        R::TextureId: Clone + 'static,
+    {
+        let Some(geometry) = self.space.output_geometry(output) else {
+            return vec![];
+        };
+
+        let location = self.seat.get_pointer().unwrap().current_location();
+        if !geometry.to_f64().contains(location) {
+            return vec![];
+        }
+
+        let scale = Scale::from(output.current_scale().fractional_scale());
+        let time = self.start_time.elapsed();
+        self.cursor
+            .render_elements(renderer, location - geometry.loc.to_f64(), scale, time)
+    }
+
+    /// Layer surfaces and windows of the output from front to back, as rendered by the space.
+    /// Windows are drawn with the opacity set by window rules.
+    pub fn output_elements<R>(
+        &self,
+        renderer: &mut R,
+        output: &Output,
+    ) -> Vec<SpaceRenderElements<R, WaylandSurfaceRenderElement<R>>>
+    where
+        R: Renderer + ImportAll + ImportMem,
+        R::TextureId: Clone + 'static,
+    {
+        let mut elements = match space_render_elements(renderer, [&self.space], output, 1.0) {
+            Ok(elements) => elements,
+            Err(err) => {
+                warn!("failed to collect render elements: {err:?}");
+                return vec![];
+            }
+        };
+
+        let Some(geometry) = self.space.output_geometry(output) else {
+            return elements;
+        };
+
+        // The space draws all windows opaque, the elements of translucent ones are swapped
+        // for the same elements drawn with their alpha
+        let scale = Scale::from(output.current_scale().fractional_scale());
+        let mut faded: Vec<WaylandSurfaceRenderElement<R>> = vec![];
+        for window in self.space.elements() {
+            let Some(opacity) = window.user_data().get::<Opacity>() else {
+                continue;
+            };
+
+            let Some(location) = self.space.element_location(window) else {
+                continue;
+            };
+
+            // Surfaces are drawn from their origin, not from their window geometry
+            let location = location - window.geometry().loc - geometry.loc;
+            faded.extend(AsRenderElements::<R>::render_elements(
+                window,
+                renderer,
+                location.to_physical_precise_round(scale),
+                scale,
+                opacity.0,
+            ));
+        }
+
+        for element in &mut elements {
+            if let Some(idx) = faded
+                .iter()
+                .position(|surface| surface.id() == element.id())
+            {
+                *element = SpaceRenderElements::Element(Wrap::from(faded.swap_remove(idx)));
+            }
+        }
+
+        elements
+    }
+
     pub fn resize_mode(&self) -> bool {
         matches!(self.current_mode, keybinding::Mode::Resize)
     }
@@ -183,8 +377,471 @@ impl State {
         let idx = self.current_workspace;
         self.workspaces.get(&idx).unwrap().clone()
     }
+
+    /// Configure and map the windows of the shown workspaces whose geometry changed
+    pub fn apply_pending_updates(&mut self) {
+        let shown: Vec<_> = self.shown_workspaces.iter().map(|(_, num)| *num).collect();
+        for num in shown {
+            self.apply_workspace_updates(num);
+        }
+    }
+
+    fn apply_workspace_updates(&mut self, num: u8) {
+        let Some(ws) = self.workspaces.get(&num).cloned() else {
+            return;
+        };
+
+        let mut ws = ws.borrow_mut();
+        let updates = ws.get_pending_updates();
+        if updates.is_empty() {
+            return;
+        }
+
+        let fullscreen = ws.fullscreen_window();
+        for (window, geometry, activate) in updates {
+            if let Some(toplevel) = window.toplevel() {
+                let is_fullscreen = fullscreen.as_ref() == Some(&window);
+                toplevel.with_pending_state(|state| {
+                    state.size = Some(geometry.size);
+                    if is_fullscreen {
+                        state.states.set(xdg_toplevel::State::Fullscreen);
+                    } else {
+                        state.states.unset(xdg_toplevel::State::Fullscreen);
+                    }
+                });
+
+                toplevel.send_configure();
+            }
+
+            self.space.map_element(window, geometry.loc, activate);
+        }
+
+        // Mapping puts elements on top of the stack, keep floating windows above tiled ones
+        for window in ws.floating() {
+            self.space.raise_element(&window, false);
+        }
+
+        for window in ws.hidden() {
+            self.space.unmap_elem(&window);
+        }
+
+        drop(ws);
+        if num == self.current_workspace {
+            self.set_top_layers_visible(fullscreen.is_none());
+        }
+    }
+
+    pub fn is_floating(&self, window: &Window) -> bool {
+        self.get_current_workspace().borrow().is_floating(window)
+    }
+
+    /// Keep the floating layer in sync with the window location and size after a commit,
+    /// they might have changed after a resize grab or a client side resize.
+    pub fn update_floating_geometry(&mut self, surface: &WlSurface) {
+        let Some(window) = self
+            .space
+            .elements()
+            .find(|w| w.toplevel().unwrap().wl_surface() == surface)
+            .cloned()
+        else {
+            return;
+        };
+
+        let ws = self.get_current_workspace();
+        let mut ws = ws.borrow_mut();
+        if !ws.is_floating(&window) {
+            return;
+        }
+
+        if let Some(location) = self.space.element_location(&window) {
+            let geometry = Rectangle::from_loc_and_size(location, window.geometry().size);
+            ws.set_floating_geometry(&window, geometry);
+        }
+    }
+
+    /// Apply the window rules matching a toplevel on its initial commit, when its app_id
+    /// is known. Unless a rule decides, toplevels having a parent or a fixed size float,
+    /// centered on their parent or on the output. Returns true if a rule sent the window
+    /// to another workspace.
+    pub fn place_toplevel(&mut self, surface: &WlSurface) -> bool {
+        // The window stays on the workspace it was opened on, even if another one is shown
+        let Some((home, window)) = self.workspaces.iter().find_map(|(num, ws)| {
+            ws.borrow()
+                .leaves()
+                .into_iter()
+                .find(|w| w.toplevel().unwrap().wl_surface() == surface)
+                .map(|window| (*num, window))
+        }) else {
+            return false;
+        };
+
+        // Only the first commit decides, later changes are left to the user
+        if is_placed(&window) {
+            return false;
+        }
+
+        window.user_data().insert_if_missing(|| Placed);
+
+        let toplevel = window.toplevel().unwrap();
+        let (app_id, title) = app_id_and_title(&window);
+        let rule = window_rule(
+            &self.config.window_rules,
+            app_id.as_deref(),
+            title.as_deref(),
+        );
+
+        if let Some(opacity) = rule.opacity {
+            window.user_data().insert_if_missing(|| Opacity(opacity));
+        }
+
+        if let Some(borders) = rule.borders {
+            window.user_data().insert_if_missing(|| Borders(borders));
+            let mode = decoration_mode(Some(borders));
+            toplevel.with_pending_state(|state| state.decoration_mode = Some(mode));
+        }
+
+        let workspace = rule.workspace.unwrap_or(home);
+        let moved = workspace != home;
+        let home = self.workspaces[&home].clone();
+        let ws = if moved {
+            home.borrow_mut().remove_matching(&window);
+            self.space.unmap_elem(&window);
+            let target = self.get_or_create_workspace(workspace);
+            target.borrow_mut().insert(window.clone());
+            target
+        } else {
+            home
+        };
+
+        let mut ws = ws.borrow_mut();
+
+        if !ws.is_floating(&window) {
+            let (min_size, max_size) = with_states(surface, |states| {
+                let mut guard = states.cached_state.get::<SurfaceCachedState>();
+                let current = guard.current();
+                (current.min_size, current.max_size)
+            });
+
+            let fixed_size = min_size.w > 0 && min_size.h > 0 && min_size == max_size;
+            let parent = toplevel.parent();
+            let floating = rule.floating.unwrap_or(parent.is_some() || fixed_size);
+
+            if floating {
+                let area = parent
+                    .and_then(|parent| {
+                        self.space
+                            .elements()
+                            .find(|w| w.toplevel().unwrap().wl_surface() == &parent)
+                            .and_then(|w| self.space.element_geometry(w))
+                    })
+                    .unwrap_or_else(|| ws.geometry());
+
+                let size = match rule.size {
+                    Some(size) => Size::from(size),
+                    None if fixed_size => min_size,
+                    None => Size::from((
+                        (area.size.w / 2).max(min_size.w),
+                        (area.size.h / 2).max(min_size.h),
+                    )),
+                };
+
+                let loc = match rule.position {
+                    Some(position) => ws.geometry().loc + Point::from(position),
+                    None => {
+                        area.loc
+                            + Point::from(((area.size.w - size.w) / 2, (area.size.h - size.h) / 2))
+                    }
+                };
+
+                ws.float(&window, Rectangle::from_loc_and_size(loc, size));
+            } else {
+                // Tiled windows can fill a loaded layout now that their app_id is known
+                ws.move_to_placeholder(&window, |matcher| {
+                    matcher.matches(app_id.as_deref(), title.as_deref())
+                });
+            }
+        }
+
+        if rule.fullscreen == Some(true) && ws.fullscreen_window().as_ref() != Some(&window) {
+            let output = self.workspace_outputs.get(&workspace);
+            if let Some(geometry) = output.and_then(|output| self.space.output_geometry(output)) {
+                ws.set_focus_matching(&window);
+                ws.toggle_fullscreen_window(geometry);
+            }
+        }
+
+        if !self.is_shown(workspace) {
+            // Hidden workspaces are laid out when switching to them, the window is not
+            // mapped so the initial configure is not sent by the commit handler
+            let updates = ws.get_pending_updates();
+            if let Some((_, geometry, _)) = updates.iter().find(|(w, ..)| w == &window) {
+                toplevel.with_pending_state(|state| state.size = Some(geometry.size));
+            }
+
+            toplevel.send_configure();
+        }
+
+        let orientation = ws.orientation();
+        drop(ws);
+
+        // Subscribers learn about the window once it is on its workspace
+        let info = self.window_info(&window);
+        self.emit_event(Event::WindowOpened(info));
+        if moved {
+            self.emit_event(Event::LayoutChanged(orientation.into()));
+        }
+
+        moved
+    }
+
+    /// Decorations for the toplevel: server side ones, so that the client draws no borders,
+    /// unless a window rule lets it draw them
+    pub fn decoration_mode(&self, surface: &WlSurface) -> DecorationMode {
+        let borders = self
+            .window_for_surface(surface)
+            .and_then(|window| window.user_data().get::<Borders>().map(|borders| borders.0));
+
+        decoration_mode(borders)
+    }
+
+    /// Geometry of the focused output, covered by fullscreen windows
+    pub fn fullscreen_geometry(&self) -> Option<Rectangle<i32, Logical>> {
+        self.focused_output()
+            .and_then(|output| self.space.output_geometry(&output))
+    }
+
+    /// Output of the current workspace, or the first one before workspaces are laid out
+    pub fn focused_output(&self) -> Option<Output> {
+        self.workspace_outputs
+            .get(&self.current_workspace)
+            .or_else(|| self.space.outputs().next())
+            .cloned()
+    }
+
+    /// Area of the output left to windows by layer surfaces, in space coordinates
+    pub fn workspace_area(&self, output: &Output) -> Rectangle<i32, Logical> {
+        let mut area = layer_map_for_output(output).non_exclusive_zone();
+        if let Some(geometry) = self.space.output_geometry(output) {
+            area.loc += geometry.loc;
+        }
+
+        area
+    }
+
+    /// Unmap top layer surfaces so they are not drawn above a fullscreen window,
+    /// or map them back.
+    pub fn set_top_layers_visible(&mut self, visible: bool) {
+        if visible {
+            for (output, layer) in self.hidden_layers.drain(..) {
+                if layer.alive() {
+                    let mut map = layer_map_for_output(&output);
+                    if let Err(err) = map.map_layer(&layer) {
+                        warn!("failed to restore layer surface: {err:?}");
+                    }
+                }
+            }
+        } else {
+            for output in self.space.outputs() {
+                let mut map = layer_map_for_output(output);
+                let top: Vec<_> = map.layers_on(Layer::Top).cloned().collect();
+                for layer in top {
+                    map.unmap_layer(&layer);
+                    self.hidden_layers.push((output.clone(), layer));
+                }
+            }
+        }
+    }
+
+    /// Get the workspace with the given index, creating it on the focused output if needed
+    pub fn get_or_create_workspace(&mut self, idx: u8) -> Rc<RefCell<Tree<Window>>> {
+        if let Some(ws) = self.workspaces.get(&idx) {
+            return ws.clone();
+        }
+
+        let output = self
+            .workspace_outputs
+            .get(&idx)
+            .cloned()
+            .or_else(|| self.focused_output());
+
+        let geometry = output
+            .as_ref()
+            .map(|output| self.workspace_area(output))
+            .unwrap_or_default();
+
+        if let Some(output) = output {
+            self.workspace_outputs.insert(idx, output);
+        }
+
+        let mut tree = Tree::new(geometry, Orientation::Horizontal);
+        tree.set_gaps(self.config.gaps);
+        let ws = Rc::new(RefCell::new(tree));
+        self.workspaces.insert(idx, ws.clone());
+        ws
+    }
+
+    pub fn is_shown(&self, num: u8) -> bool {
+        self.shown_workspaces.iter().any(|(_, shown)| *shown == num)
+    }
+
+    /// Show the workspace on its output, in place of the one shown there which is unmapped.
+    /// Returns the replaced workspace.
+    pub fn show_workspace(&mut self, num: u8) -> Option<u8> {
+        if self.is_shown(num) {
+            return None;
+        }
+
+        let output = self.workspace_outputs.get(&num)?.clone();
+        let previous = match self.shown_workspaces.iter_mut().find(|(o, _)| o == &output) {
+            Some((_, shown)) => Some(mem::replace(shown, num)),
+            None => {
+                self.shown_workspaces.push((output, num));
+                None
+            }
+        };
+
+        if let Some(previous) = previous.and_then(|num| self.workspaces.get(&num)) {
+            for window in previous.borrow().leaves() {
+                self.space.unmap_elem(&window);
+            }
+        }
+
+        // Windows will be mapped on the next event loop dispatch
+        if let Some(ws) = self.workspaces.get(&num) {
+            ws.borrow_mut().refresh();
+        }
+
+        previous
+    }
+
+    /// Show a workspace on a newly mapped output: the ones left without an output move
+    /// there, otherwise a new workspace with the lowest free number is created.
+    pub fn add_output_workspace(&mut self, output: &Output) {
+        if self.shown_workspaces.iter().any(|(o, _)| o == output) {
+            return;
+        }
+
+        let mut orphans: Vec<_> = self
+            .workspaces
+            .keys()
+            .filter(|num| !self.workspace_outputs.contains_key(num))
+            .copied()
+            .collect();
+        orphans.sort();
+
+        let area = self.workspace_area(output);
+        for num in &orphans {
+            let mut ws = self.workspaces[num].borrow_mut();
+            ws.set_geometry(area);
+            // Laid out again when shown
+            ws.get_pending_updates();
+            self.workspace_outputs.insert(*num, output.clone());
+        }
+
+        let num = if orphans.contains(&self.current_workspace) {
+            self.current_workspace
+        } else if let Some(num) = orphans.first() {
+            *num
+        } else {
+            let Some(num) = (1..=u8::MAX).find(|num| !self.workspaces.contains_key(num)) else {
+                return;
+            };
+
+            self.workspace_outputs.insert(num, output.clone());
+            self.get_or_create_workspace(num);
+            num
+        };
+
+        self.show_workspace(num);
+    }
+
+    /// Move the workspaces of an unmapped output to the first remaining one, the current
+    /// workspace is shown there in place of the workspace the output had
+    pub fn remove_output(&mut self, output: &Output) {
+        self.shown_workspaces.retain(|(o, _)| o != output);
+        let target = self
+            .space
+            .outputs()
+            .find(|o| *o != output)
+            .cloned()
+            .map(|target| {
+                let area = self.workspace_area(&target);
+                (target, area)
+            });
+
+        let moved: Vec<_> = self
+            .workspace_outputs
+            .iter()
+            .filter(|(_, o)| *o == output)
+            .map(|(num, _)| *num)
+            .collect();
+
+        for num in &moved {
+            let mut ws = self.workspaces[num].borrow_mut();
+            for window in ws.leaves() {
+                self.space.unmap_elem(&window);
+            }
+
+            match &target {
+                Some((target, area)) => {
+                    ws.set_geometry(*area);
+                    self.workspace_outputs.insert(*num, target.clone());
+                }
+                None => {
+                    self.workspace_outputs.remove(num);
+                }
+            }
+
+            // Laid out again when shown
+            ws.get_pending_updates();
+        }
+
+        if moved.contains(&self.current_workspace) {
+            self.show_workspace(self.current_workspace);
+        }
+    }
+}
+
+fn decoration_mode(borders: Option<bool>) -> DecorationMode {
+    if borders == Some(true) {
+        DecorationMode::ClientSide
+    } else {
+        DecorationMode::ServerSide
+    }
 }
 
+/// The app_id and title of the toplevel, as last committed by its client
+pub fn app_id_and_title(window: &Window) -> (Option<String>, Option<String>) {
+    let Some(toplevel) = window.toplevel() else {
+        return (None, None);
+    };
+
+    with_states(toplevel.wl_surface(), |states| {
+        let data = states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap();
+        (data.app_id.clone(), data.title.clone())
+    })
+}
+
+/// Whether the toplevel already went through initial placement, subscribers were told
+/// about it then
+pub fn is_placed(window: &Window) -> bool {
+    window.user_data().get::<Placed>().is_some()
+}
+
+/// Marks toplevels that already went through initial placement
+struct Placed;
+
+/// Alpha the window is drawn with, set by window rules
+struct Opacity(f32);
+
+/// Whether the client draws its borders, set by window rules
+struct Borders(bool);
+
 #[derive(Default)]
 pub struct ClientState {
     pub compositor_state: CompositorClientState,
@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use smithay::reexports::calloop::generic::Generic;
+use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+use tracing::{debug, warn};
+
+use crate::Wzm;
+
+/// Reload the config when the file or one of its includes is written. Their directories
+/// are watched rather than the files, so that editors replacing the file on save are noticed.
+pub(crate) fn init_config_watcher(event_loop: &LoopHandle<Wzm>, path: &Path, sources: &[PathBuf]) {
+    let inotify = match Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC) {
+        Ok(inotify) => inotify,
+        Err(err) => {
+            warn!("Failed to watch the config, it is only reloaded on demand: {err}");
+            return;
+        }
+    };
+
+    let mut dirs = vec![];
+    watch_dirs(&inotify, &mut dirs, &config_files(path, sources));
+
+    event_loop
+        .insert_source(
+            Generic::new(inotify, Interest::READ, Mode::Level),
+            move |_, inotify, state| {
+                // Safety: the inotify instance is not dropped
+                let inotify = unsafe { inotify.get_mut() };
+                let events = match inotify.read_events() {
+                    Ok(events) => events,
+                    Err(Errno::EAGAIN) => return Ok(PostAction::Continue),
+                    Err(err) => {
+                        warn!("Failed to read config changes: {err}");
+                        return Ok(PostAction::Continue);
+                    }
+                };
+
+                let files = config_files(&state.state.config_path, &state.state.config.sources);
+                if events
+                    .iter()
+                    .any(|event| is_config_change(event, &dirs, &files))
+                {
+                    state.reload_config();
+
+                    // Includes added by the new config are watched from now on
+                    let files = config_files(&state.state.config_path, &state.state.config.sources);
+                    watch_dirs(inotify, &mut dirs, &files);
+                }
+
+                Ok(PostAction::Continue)
+            },
+        )
+        .expect("Failed to init the config watcher.");
+}
+
+/// The config file and its includes, resolved as when loading them
+fn config_files(path: &Path, sources: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    files.extend(sources.iter().cloned());
+    files
+}
+
+fn watch_dirs(inotify: &Inotify, dirs: &mut Vec<(WatchDescriptor, PathBuf)>, files: &[PathBuf]) {
+    let flags = AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO;
+    let dirs_of_files = files
+        .iter()
+        .filter_map(|file| file.parent())
+        .filter(|dir| !dir.as_os_str().is_empty());
+
+    for dir in dirs_of_files {
+        if dirs.iter().any(|(_, watched)| watched == dir) {
+            continue;
+        }
+
+        match inotify.add_watch(dir, flags) {
+            Ok(wd) => {
+                debug!("watching {dir:?} for config changes");
+                dirs.push((wd, dir.to_path_buf()));
+            }
+            Err(err) => warn!("Failed to watch {dir:?} for config changes: {err}"),
+        }
+    }
+}
+
+fn is_config_change(
+    event: &InotifyEvent,
+    dirs: &[(WatchDescriptor, PathBuf)],
+    files: &[PathBuf],
+) -> bool {
+    if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+        return true;
+    }
+
+    let Some(name) = &event.name else {
+        return false;
+    };
+
+    dirs.iter()
+        .filter(|(wd, _)| *wd == event.wd)
+        .any(|(_, dir)| files.contains(&dir.join(name)))
+}
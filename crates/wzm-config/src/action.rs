@@ -1,5 +1,6 @@
-use crate::keybinding::{ResizeDirection, ResizeType};
+use crate::keybinding::{Mode, ResizeDirection, ResizeType};
 use smithay::utils::{Logical, Point};
+use std::path::PathBuf;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum KeyAction {
@@ -16,6 +17,7 @@ pub enum KeyAction {
     MoveFocus(Direction),
     Run(String, Vec<(String, String)>),
     MoveToWorkspace(u8),
+    MoveWindowToWorkspace(u8),
     ToggleSwitchLayout,
     LayoutVertical,
     LayoutHorizontal,
@@ -23,9 +25,15 @@ pub enum KeyAction {
     VtSwitch(i32),
     CloseWindow,
     Quit,
+    Reload,
+    CycleKeyboardLayout,
     None,
     ToggleResize,
     Resize(ResizeType, ResizeDirection, u32),
+    EnterMode(Mode),
+    ExitMode,
+    SaveLayout(PathBuf),
+    LoadLayout(PathBuf),
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
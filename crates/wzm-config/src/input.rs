@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use crate::loader::some;
+
+/// libinput settings for pointer devices, unset fields keep the device defaults.
+/// Entries without a name apply to every device, then the ones matching
+/// the device name are applied on top of them in order.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputConfig {
+    /// Device name as reported by libinput, e.g. `"SynPS/2 Synaptics TouchPad"`
+    #[serde(default, deserialize_with = "some")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "some")]
+    pub accel_profile: Option<AccelProfile>,
+    /// Pointer acceleration, between -1.0 and 1.0
+    #[serde(default, deserialize_with = "some")]
+    pub accel_speed: Option<f64>,
+    #[serde(default, deserialize_with = "some")]
+    pub natural_scroll: Option<bool>,
+    #[serde(default, deserialize_with = "some")]
+    pub tap: Option<bool>,
+    #[serde(default, deserialize_with = "some")]
+    pub left_handed: Option<bool>,
+    #[serde(default, deserialize_with = "some")]
+    pub disable_while_typing: Option<bool>,
+    #[serde(default, deserialize_with = "some")]
+    pub scroll_method: Option<ScrollMethod>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AccelProfile {
+    Flat,
+    Adaptive,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ScrollMethod {
+    NoScroll,
+    TwoFinger,
+    Edge,
+    OnButtonDown,
+}
+
+impl InputConfig {
+    pub fn matches(&self, device_name: &str) -> bool {
+        match &self.name {
+            Some(name) => name == device_name,
+            None => true,
+        }
+    }
+
+    /// Overwrite the settings defined in `other`
+    pub fn merge(&mut self, other: &InputConfig) {
+        self.accel_profile = other.accel_profile.or(self.accel_profile);
+        self.accel_speed = other.accel_speed.or(self.accel_speed);
+        self.natural_scroll = other.natural_scroll.or(self.natural_scroll);
+        self.tap = other.tap.or(self.tap);
+        self.left_handed = other.left_handed.or(self.left_handed);
+        self.disable_while_typing = other.disable_while_typing.or(self.disable_while_typing);
+        self.scroll_method = other.scroll_method.or(self.scroll_method);
+    }
+}
+
+/// Settings for a device, merged from every matching entry
+pub fn device_config(configs: &[InputConfig], device_name: &str) -> InputConfig {
+    let generic = configs.iter().filter(|config| config.name.is_none());
+    let named = configs.iter().filter(|config| config.name.is_some());
+
+    let mut merged = InputConfig::default();
+    for config in generic.chain(named) {
+        if config.matches(device_name) {
+            merged.merge(config);
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use speculoos::prelude::*;
+
+    use crate::input::{device_config, InputConfig, ScrollMethod};
+
+    #[test]
+    fn should_merge_named_device_over_generic() {
+        let configs = vec![
+            InputConfig {
+                name: Some("touchpad".to_string()),
+                natural_scroll: Some(true),
+                ..Default::default()
+            },
+            InputConfig {
+                tap: Some(true),
+                natural_scroll: Some(false),
+                scroll_method: Some(ScrollMethod::TwoFinger),
+                ..Default::default()
+            },
+        ];
+
+        let touchpad = device_config(&configs, "touchpad");
+        let mouse = device_config(&configs, "mouse");
+
+        assert_that!(touchpad.natural_scroll).is_equal_to(Some(true));
+        assert_that!(touchpad.tap).is_equal_to(Some(true));
+        assert_that!(mouse.natural_scroll).is_equal_to(Some(false));
+        assert_that!(mouse.scroll_method).is_equal_to(Some(ScrollMethod::TwoFinger));
+    }
+}
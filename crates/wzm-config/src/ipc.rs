@@ -0,0 +1,189 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use smithay::utils::{Logical, Rectangle};
+
+use crate::keybinding::Action;
+
+/// Environment variable holding the IPC socket path, set for every client started by wzm
+pub const SOCKET_ENV: &str = "WZM_SOCK";
+
+/// Request sent to the compositor, as a single line of JSON.
+/// Each request gets a [`Response`] line back on the same connection.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Request {
+    /// Run any bindable action, e.g. `{"Action":"MoveFocusLeft"}`
+    Action(Action),
+    GetWorkspaces,
+    GetFocusedWindow,
+    /// Layout of the current workspace
+    GetTree,
+    /// Name of the current binding mode, changes are sent as [`Event::ModeChanged`]
+    GetMode,
+    /// Receive the given kinds of [`Event`]s on this connection, or all of them if empty
+    Subscribe(Vec<EventKind>),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Response {
+    Ok,
+    Error(String),
+    Workspaces(Vec<WorkspaceInfo>),
+    FocusedWindow(Option<WindowInfo>),
+    Tree(TreeInfo),
+    Mode(String),
+}
+
+/// Sent to subscribed clients as they happen, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Event {
+    WindowOpened(WindowInfo),
+    WindowClosed(WindowInfo),
+    /// The keyboard focus changed, `None` if it left every window
+    WindowFocused(Option<WindowInfo>),
+    WindowTitleChanged(WindowInfo),
+    WorkspaceSwitched(u8),
+    /// Name of the new binding mode
+    ModeChanged(String),
+    /// Orientation of the focused container
+    LayoutChanged(Orientation),
+    ConfigReloaded,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EventKind {
+    Window,
+    Workspace,
+    Mode,
+    Layout,
+    Config,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::WindowOpened(_)
+            | Event::WindowClosed(_)
+            | Event::WindowFocused(_)
+            | Event::WindowTitleChanged(_) => EventKind::Window,
+            Event::WorkspaceSwitched(_) => EventKind::Workspace,
+            Event::ModeChanged(_) => EventKind::Mode,
+            Event::LayoutChanged(_) => EventKind::Layout,
+            Event::ConfigReloaded => EventKind::Config,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WorkspaceInfo {
+    pub num: u8,
+    pub focused: bool,
+    pub windows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WindowInfo {
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub geometry: Geometry,
+    pub focused: bool,
+    pub floating: bool,
+    pub fullscreen: bool,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl From<Rectangle<i32, Logical>> for Geometry {
+    fn from(rect: Rectangle<i32, Logical>) -> Self {
+        Geometry {
+            x: rect.loc.x,
+            y: rect.loc.y,
+            width: rect.size.w,
+            height: rect.size.h,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TreeInfo {
+    pub workspace: u8,
+    pub root: NodeInfo,
+    /// Floating windows, from the bottom to the top of the stack
+    pub floating: Vec<WindowInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum NodeInfo {
+    Container {
+        orientation: Orientation,
+        geometry: Geometry,
+        ratio: Option<f32>,
+        children: Vec<NodeInfo>,
+    },
+    Window {
+        ratio: Option<f32>,
+        window: WindowInfo,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Orientation {
+    Vertical,
+    Horizontal,
+}
+
+/// Socket of the compositor running on the given wayland display:
+/// `$XDG_RUNTIME_DIR/wzm-<wayland display>.sock`
+pub fn socket_path(wayland_display: &OsStr) -> Option<PathBuf> {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")?;
+    let mut name = OsString::from("wzm-");
+    name.push(wayland_display);
+    name.push(".sock");
+    Some(PathBuf::from(runtime_dir).join(name))
+}
+
+#[cfg(test)]
+mod test {
+    use speculoos::prelude::*;
+
+    use crate::ipc::{Event, EventKind, Request, Response};
+    use crate::keybinding::Action;
+
+    #[test]
+    fn should_parse_action_request() {
+        let request: Request =
+            serde_json::from_str(r#"{"Action":{"Run":{"env":[],"command":"foot"}}}"#).unwrap();
+
+        assert_that!(request).is_equal_to(Request::Action(Action::Run {
+            env: vec![],
+            command: "foot".to_string(),
+        }));
+    }
+
+    #[test]
+    fn should_parse_subscription() {
+        let request: Request = serde_json::from_str(r#"{"Subscribe":["Window","Mode"]}"#).unwrap();
+
+        assert_that!(request)
+            .is_equal_to(Request::Subscribe(vec![EventKind::Window, EventKind::Mode]));
+        assert_that!(serde_json::to_string(&Event::ModeChanged("resize".to_string())).unwrap())
+            .is_equal_to(r#"{"ModeChanged":"resize"}"#.to_string());
+    }
+
+    #[test]
+    fn should_answer_current_mode() {
+        let request: Request = serde_json::from_str(r#""GetMode""#).unwrap();
+
+        assert_that!(request).is_equal_to(Request::GetMode);
+        assert_that!(serde_json::to_string(&Response::Mode("launch".to_string())).unwrap())
+            .is_equal_to(r#"{"Mode":"launch"}"#.to_string());
+    }
+}
@@ -1,28 +1,110 @@
 use crate::action::{Direction, KeyAction};
+use crate::loader::some;
 use log::warn;
 use serde::{Deserialize, Serialize, Serializer};
 use smithay::input::keyboard::ModifiersState;
 use std::collections::HashSet;
+use std::fmt;
 use std::hash::Hash;
+use std::path::PathBuf;
 use xkbcommon::xkb;
 use xkbcommon::xkb::Keysym;
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct KeyBinding {
     pub modifiers: HashSet<Modifier>,
+    /// Matched against the symbol produced with the active layout, and against the
+    /// unmodified symbols of the first layout so Shift or a non-Latin layout don't
+    /// change the binding
+    #[serde(default = "no_symbol")]
     #[serde(serialize_with = "serialize_key")]
     #[serde(deserialize_with = "deserialize_key")]
     pub key: Keysym,
+    /// Raw XKB keycode, as reported by `wev`, matched instead of `key` when set
+    #[serde(default, deserialize_with = "some")]
+    pub code: Option<u32>,
     pub action: Action,
     #[serde(default)]
     pub mode: Mode,
+    /// Keys to press after the first one to trigger the action, e.g. `Logo+w` then `h`
+    #[serde(default)]
+    pub then: Vec<KeyPress>,
+    /// Trigger the action when the key is released, only if no other key was pressed
+    /// in between. The modifiers are the ones still held after the release, so a bare
+    /// modifier tap is bound with `key: "Super_L"`, `modifiers: []` and `release: true`.
+    #[serde(default)]
+    pub release: bool,
+}
+
+/// A key pressed with some modifiers, one step of a key sequence
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KeyPress {
+    #[serde(default)]
+    pub modifiers: HashSet<Modifier>,
+    #[serde(default = "no_symbol")]
+    #[serde(serialize_with = "serialize_key")]
+    #[serde(deserialize_with = "deserialize_key")]
+    pub key: Keysym,
+    #[serde(default, deserialize_with = "some")]
+    pub code: Option<u32>,
+}
+
+/// A key event as seen by the bindings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    /// Symbol produced with the active layout and modifiers
+    pub sym: Keysym,
+    /// Symbols of the first layout, without modifiers applied
+    pub raw_syms: Vec<Keysym>,
+    /// Raw XKB keycode
+    pub code: u32,
+}
+
+impl Key {
+    fn matches(&self, key: Keysym, code: Option<u32>) -> bool {
+        match code {
+            Some(code) => code == self.code,
+            None => key == self.sym || self.raw_syms.contains(&key),
+        }
+    }
+}
+
+/// How the keys typed so far relate to a binding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceMatch {
+    /// Every key of the binding was pressed
+    Complete(Action),
+    /// The keys are the beginning of the binding sequence
+    Partial,
+    NoMatch,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize, Default)]
 pub enum Mode {
     #[default]
     Normal,
     Resize,
+    /// A mode declared in the `modes` section of the config
+    Named(String),
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Normal => write!(f, "Normal"),
+            Mode::Resize => write!(f, "Resize"),
+            Mode::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// A user defined binding mode
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ModeConfig {
+    pub name: String,
+    /// Return to the previous mode once a binding of this mode was triggered
+    #[serde(default)]
+    pub oneshot: bool,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -38,15 +120,49 @@ pub enum ResizeDirection {
 }
 
 impl KeyBinding {
-    pub fn match_action(
+    pub fn match_action(&self, modifiers: ModifiersState, key: Key, mode: &Mode) -> Option<Action> {
+        match self.match_sequence(&[(modifiers, key)], mode) {
+            SequenceMatch::Complete(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    /// Match the keys pressed so far, oldest first, against the binding sequence
+    pub fn match_sequence(&self, keys: &[(ModifiersState, Key)], mode: &Mode) -> SequenceMatch {
+        if self.release || mode != &self.mode || keys.len() > self.then.len() + 1 {
+            return SequenceMatch::NoMatch;
+        }
+
+        let matches =
+            keys.iter()
+                .zip(self.steps())
+                .all(|((modifiers, pressed), (state, key, code))| {
+                    match_modifier(state, *modifiers) && pressed.matches(key, code)
+                });
+
+        if !matches {
+            SequenceMatch::NoMatch
+        } else if keys.len() == self.then.len() + 1 {
+            SequenceMatch::Complete(self.action.clone())
+        } else {
+            SequenceMatch::Partial
+        }
+    }
+
+    /// Match a key release against a release binding
+    pub fn match_release(
         &self,
         modifiers: ModifiersState,
-        key: Keysym,
-        mode: Mode,
+        key: &Key,
+        mode: &Mode,
     ) -> Option<Action> {
         let state: ModifiersState = self.into();
 
-        if match_modifier(state, modifiers) && key == self.key && mode == self.mode {
+        if self.release
+            && match_modifier(state, modifiers)
+            && key.matches(self.key, self.code)
+            && mode == &self.mode
+        {
             Some(self.action.clone())
         } else {
             None
@@ -54,6 +170,130 @@ impl KeyBinding {
     }
 }
 
+impl KeyBinding {
+    /// Whether both bindings are triggered by the same keys and modifiers in the same mode
+    pub fn same_trigger(&self, other: &KeyBinding) -> bool {
+        self.modifiers == other.modifiers
+            && self.key == other.key
+            && self.code == other.code
+            && self.then == other.then
+            && self.release == other.release
+            && self.mode == other.mode
+    }
+
+    /// Whether typing the keys of this binding in the same mode also starts the other one,
+    /// which then never fires: this binding completes first. `raw_syms` gives the symbols
+    /// of a keycode, so that a `code` and a `key` on the same physical key are compared.
+    pub fn shadows(&self, other: &KeyBinding, raw_syms: impl Fn(u32) -> Vec<Keysym>) -> bool {
+        if self.release != other.release
+            || self.mode != other.mode
+            || self.then.len() > other.then.len()
+        {
+            return false;
+        }
+
+        self.steps().zip(other.steps()).all(
+            |((modifiers, key, code), (other_modifiers, other_key, other_code))| {
+                let same_key = match (code, other_code) {
+                    (Some(code), Some(other_code)) => code == other_code,
+                    (Some(code), None) => raw_syms(code).contains(&other_key),
+                    (None, Some(other_code)) => raw_syms(other_code).contains(&key),
+                    (None, None) => key == other_key,
+                };
+
+                same_key && match_modifier(modifiers, other_modifiers)
+            },
+        )
+    }
+
+    fn steps(&self) -> impl Iterator<Item = (ModifiersState, Keysym, Option<u32>)> + '_ {
+        let first = (modifiers_state(&self.modifiers), self.key, self.code);
+        std::iter::once(first).chain(
+            self.then
+                .iter()
+                .map(|press| (modifiers_state(&press.modifiers), press.key, press.code)),
+        )
+    }
+}
+
+/// Two bindings sharing the same trigger in a config file, or a binding whose keys start
+/// a longer sequence. Only the first one is ever matched, the second one is shadowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub modifiers: HashSet<Modifier>,
+    pub key: Keysym,
+    pub code: Option<u32>,
+    pub then: Vec<KeyPress>,
+    pub release: bool,
+    pub mode: Mode,
+    pub first: Action,
+    pub shadowed: Action,
+}
+
+impl BindingConflict {
+    pub fn new(first: &KeyBinding, shadowed: KeyBinding) -> Self {
+        Self {
+            modifiers: shadowed.modifiers,
+            key: shadowed.key,
+            code: shadowed.code,
+            then: shadowed.then,
+            release: shadowed.release,
+            mode: shadowed.mode,
+            first: first.action.clone(),
+            shadowed: shadowed.action,
+        }
+    }
+
+    /// Both bindings trigger the same action
+    pub fn is_duplicate(&self) -> bool {
+        self.first == self.shadowed
+    }
+}
+
+impl fmt::Display for BindingConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_key(f, &self.modifiers, self.key, self.code)?;
+        for press in &self.then {
+            write!(f, " ")?;
+            write_key(f, &press.modifiers, press.key, press.code)?;
+        }
+
+        if self.release {
+            write!(f, " release")?;
+        }
+
+        write!(f, " in {} mode ", self.mode)?;
+
+        if self.is_duplicate() {
+            write!(f, "is bound twice to {:?}", self.first)
+        } else {
+            write!(
+                f,
+                "is bound to {:?}, shadowing {:?}",
+                self.first, self.shadowed
+            )
+        }
+    }
+}
+
+fn write_key(
+    f: &mut fmt::Formatter<'_>,
+    modifiers: &HashSet<Modifier>,
+    key: Keysym,
+    code: Option<u32>,
+) -> fmt::Result {
+    let mut modifiers: Vec<_> = modifiers.iter().collect();
+    modifiers.sort();
+    for modifier in modifiers {
+        write!(f, "{modifier:?}+")?;
+    }
+
+    match code {
+        Some(code) => write!(f, "code:{code}"),
+        None => write!(f, "{}", xkb::keysym_get_name(key)),
+    }
+}
+
 fn match_modifier(modifier: ModifiersState, other: ModifiersState) -> bool {
     (
         modifier.ctrl,
@@ -74,16 +314,20 @@ fn match_modifier(modifier: ModifiersState, other: ModifiersState) -> bool {
 
 impl From<&KeyBinding> for ModifiersState {
     fn from(val: &KeyBinding) -> Self {
-        ModifiersState {
-            ctrl: val.modifiers.contains(&Modifier::Ctrl),
-            alt: val.modifiers.contains(&Modifier::Alt),
-            shift: val.modifiers.contains(&Modifier::Shift),
-            caps_lock: val.modifiers.contains(&Modifier::CapsLock),
-            logo: val.modifiers.contains(&Modifier::Logo),
-            num_lock: val.modifiers.contains(&Modifier::NumLock),
-            iso_level3_shift: false,
-            serialized: Default::default(),
-        }
+        modifiers_state(&val.modifiers)
+    }
+}
+
+fn modifiers_state(modifiers: &HashSet<Modifier>) -> ModifiersState {
+    ModifiersState {
+        ctrl: modifiers.contains(&Modifier::Ctrl),
+        alt: modifiers.contains(&Modifier::Alt),
+        shift: modifiers.contains(&Modifier::Shift),
+        caps_lock: modifiers.contains(&Modifier::CapsLock),
+        logo: modifiers.contains(&Modifier::Logo),
+        num_lock: modifiers.contains(&Modifier::NumLock),
+        iso_level3_shift: false,
+        serialized: Default::default(),
     }
 }
 
@@ -104,6 +348,7 @@ pub enum Action {
     MoveFocusDown,
     MoveFocusUp,
     MoveToWorkspace(u8),
+    MoveWindowToWorkspace(u8),
     LayoutVertical,
     LayoutHorizontal,
     ToggleSwitchLayout,
@@ -116,6 +361,15 @@ pub enum Action {
     },
     CloseWindow,
     Quit,
+    Reload,
+    CycleKeyboardLayout,
+    EnterMode(Mode),
+    ExitMode,
+    /// Write the tiled layout of the current workspace to a RON file,
+    /// relative paths are resolved from the config directory
+    SaveLayout(PathBuf),
+    /// Append a layout written by `SaveLayout` to the current workspace
+    LoadLayout(PathBuf),
 }
 
 impl From<Action> for KeyAction {
@@ -134,17 +388,24 @@ impl From<Action> for KeyAction {
             Action::MoveFocusDown => KeyAction::MoveFocus(Direction::Down),
             Action::MoveFocusUp => KeyAction::MoveFocus(Direction::Up),
             Action::MoveToWorkspace(num) => KeyAction::MoveToWorkspace(num),
+            Action::MoveWindowToWorkspace(num) => KeyAction::MoveWindowToWorkspace(num),
             Action::LayoutVertical => KeyAction::LayoutVertical,
             Action::LayoutHorizontal => KeyAction::LayoutHorizontal,
             Action::ToggleFloating => KeyAction::ToggleFloating,
             Action::Run { command, env } => KeyAction::Run(command, env),
             Action::CloseWindow => KeyAction::CloseWindow,
             Action::Quit => KeyAction::Quit,
+            Action::Reload => KeyAction::Reload,
+            Action::CycleKeyboardLayout => KeyAction::CycleKeyboardLayout,
+            Action::EnterMode(mode) => KeyAction::EnterMode(mode),
+            Action::ExitMode => KeyAction::ExitMode,
             Action::ToggleFullScreenWindow => KeyAction::ToggleFullScreenWindow,
             Action::ToggleFullScreenContainer => KeyAction::ToggleFullScreenContainer,
             Action::ToggleResize => KeyAction::ToggleResize,
             Action::Resize(kind, direction, ammount) => KeyAction::Resize(direction, kind, ammount),
             Action::ToggleSwitchLayout => KeyAction::ToggleSwitchLayout,
+            Action::SaveLayout(path) => KeyAction::SaveLayout(path),
+            Action::LoadLayout(path) => KeyAction::LoadLayout(path),
         }
     }
 }
@@ -155,7 +416,7 @@ pub struct EnvVar {
     value: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub enum Modifier {
     Ctrl,
     Alt,
@@ -165,6 +426,23 @@ pub enum Modifier {
     NumLock,
 }
 
+impl Modifier {
+    pub fn is_pressed(&self, state: &ModifiersState) -> bool {
+        match self {
+            Modifier::Ctrl => state.ctrl,
+            Modifier::Alt => state.alt,
+            Modifier::Shift => state.shift,
+            Modifier::Logo => state.logo,
+            Modifier::CapsLock => state.caps_lock,
+            Modifier::NumLock => state.num_lock,
+        }
+    }
+}
+
+fn no_symbol() -> Keysym {
+    Keysym::NoSymbol
+}
+
 fn serialize_key<S>(key: &Keysym, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -181,31 +459,34 @@ where
     use serde::de::{Error, Unexpected};
 
     let name = String::deserialize(deserializer)?;
+    // Unknown names resolve to `NoSymbol`
     match xkb::keysym_from_name(&name, xkb::keysyms::KEY_NoSymbol) {
-        Keysym::VoidSymbol => match xkb::keysym_from_name(&name, xkb::KEYSYM_CASE_INSENSITIVE) {
-            Keysym::VoidSymbol => Err(<D::Error as Error>::invalid_value(
-                Unexpected::Str(&name),
-                &"xkb keysym",
-            )),
-            key => {
-                warn!(
-                    "Key-Binding '{}' only matched case insensitive for {:?}",
-                    name,
-                    xkb::keysym_get_name(key)
-                );
-                Ok(key)
+        Keysym::NoSymbol | Keysym::VoidSymbol => {
+            match xkb::keysym_from_name(&name, xkb::KEYSYM_CASE_INSENSITIVE) {
+                Keysym::NoSymbol | Keysym::VoidSymbol => Err(<D::Error as Error>::invalid_value(
+                    Unexpected::Str(&name),
+                    &"a valid xkb keysym name",
+                )),
+                key => {
+                    warn!(
+                        "Key-Binding '{}' only matched case insensitive for {:?}",
+                        name,
+                        xkb::keysym_get_name(key)
+                    );
+                    Ok(key)
+                }
             }
-        },
+        }
         key => Ok(key),
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::keybinding::{Action, KeyBinding, Modifier};
-    use crate::WzmConfig;
+    use crate::keybinding::{Action, Key, KeyBinding, KeyPress, Mode, Modifier, SequenceMatch};
+    use crate::{Gaps, WzmConfig};
     use indoc::indoc;
-    use smithay::input::keyboard::Keysym;
+    use smithay::input::keyboard::{Keysym, ModifiersState};
     use speculoos::prelude::*;
     use std::collections::HashSet;
 
@@ -238,7 +519,86 @@ mod test {
     }
 
     #[test]
-    pub fn test() {
+    fn should_match_key_sequence() {
+        let binding = KeyBinding {
+            modifiers: HashSet::from([Modifier::Logo]),
+            key: Keysym::w,
+            code: None,
+            action: Action::MoveFocusLeft,
+            mode: Default::default(),
+            then: vec![KeyPress {
+                modifiers: HashSet::new(),
+                key: Keysym::h,
+                code: None,
+            }],
+            release: false,
+        };
+
+        let logo = ModifiersState {
+            logo: true,
+            ..Default::default()
+        };
+        let none = ModifiersState::default();
+        let mode = Mode::Normal;
+
+        assert_that!(binding.match_sequence(&[(logo, key(Keysym::w))], &mode))
+            .is_equal_to(SequenceMatch::Partial);
+        assert_that!(
+            binding.match_sequence(&[(logo, key(Keysym::w)), (none, key(Keysym::h))], &mode)
+        )
+        .is_equal_to(SequenceMatch::Complete(Action::MoveFocusLeft));
+        assert_that!(
+            binding.match_sequence(&[(logo, key(Keysym::w)), (none, key(Keysym::l))], &mode)
+        )
+        .is_equal_to(SequenceMatch::NoMatch);
+        assert_that!(binding.match_action(logo, key(Keysym::w), &mode)).is_none();
+    }
+
+    #[test]
+    fn should_match_first_layout_and_keycodes() {
+        let mut binding = KeyBinding {
+            modifiers: HashSet::from([Modifier::Alt]),
+            key: Keysym::_1,
+            code: None,
+            action: Action::MoveToWorkspace(1),
+            mode: Default::default(),
+            then: vec![],
+            release: false,
+        };
+
+        let alt = ModifiersState {
+            alt: true,
+            ..Default::default()
+        };
+        let mode = Mode::Normal;
+        // `1` typed with a french layout first, while another layout is active
+        let pressed = Key {
+            sym: Keysym::ampersand,
+            raw_syms: vec![Keysym::_1],
+            code: 10,
+        };
+
+        assert_that!(binding.match_action(alt, pressed.clone(), &mode))
+            .is_equal_to(Some(Action::MoveToWorkspace(1)));
+
+        binding.key = Keysym::NoSymbol;
+        binding.code = Some(10);
+        assert_that!(binding.match_action(alt, pressed.clone(), &mode)).is_some();
+
+        binding.code = Some(11);
+        assert_that!(binding.match_action(alt, pressed, &mode)).is_none();
+    }
+
+    fn key(sym: Keysym) -> Key {
+        Key {
+            sym,
+            raw_syms: vec![sym],
+            code: 0,
+        }
+    }
+
+    #[test]
+    fn should_serialize_keys_by_name() {
         let binding = vec![
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
@@ -247,7 +607,10 @@ mod test {
                     env: vec![],
                     command: "alacritty".to_string(),
                 },
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
@@ -256,70 +619,109 @@ mod test {
                     env: vec![("WGPU_BACKEND".into(), "vulkan".into())],
                     command: "onagre".to_string(),
                 },
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::a,
                 action: Action::CloseWindow,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::v,
                 action: Action::LayoutVertical,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::d,
                 action: Action::LayoutHorizontal,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Ctrl, Modifier::Shift]),
                 key: Keysym::space,
                 action: Action::ToggleFloating,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::k,
                 action: Action::MoveFocusUp,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::h,
                 action: Action::MoveFocusLeft,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::l,
                 action: Action::MoveFocusRight,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::j,
                 action: Action::MoveFocusDown,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
             KeyBinding {
                 modifiers: HashSet::from([Modifier::Alt]),
                 key: Keysym::k,
                 action: Action::MoveFocusUp,
+                code: None,
                 mode: Default::default(),
+                then: vec![],
+                release: false,
             },
         ];
 
         let config = WzmConfig {
-            gaps: 14,
+            gaps: Gaps {
+                inner: 14,
+                outer: 14,
+                smart: false,
+            },
             keybindings: binding,
+            main_modifier: Modifier::Alt,
+            ..WzmConfig::default()
         };
 
         let string = ron::to_string(&config).unwrap();
-        println!("{}", string);
+
+        assert_that!(string).contains(r#"key:"j",code:None,action:MoveFocusDown"#);
+        assert_that!(string).contains(r#"key:"k",code:None,action:MoveFocusUp"#);
+        assert_that!(string).contains("main_modifier:Alt");
     }
 }
@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use ron::extensions::Extensions;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::ipc::Orientation;
+use crate::loader::some;
+use crate::matcher::WindowMatcher;
+
+/// Tiled layout of a workspace, written with `SaveLayout` and appended to a workspace
+/// with `LoadLayout`. Windows of a loaded layout are placeholders, filled by the first
+/// mapped toplevel they match.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Layout {
+    Container {
+        orientation: Orientation,
+        /// Share of the parent container, split evenly with the other unset siblings if unset
+        #[serde(default, deserialize_with = "some")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ratio: Option<f32>,
+        children: Vec<Layout>,
+    },
+    Window {
+        #[serde(default, deserialize_with = "some")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ratio: Option<f32>,
+        #[serde(default)]
+        matcher: WindowMatcher,
+    },
+}
+
+impl Layout {
+    pub fn load(path: &Path) -> anyhow::Result<Layout> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        ron::from_str(&content).with_context(|| format!("invalid layout {}", path.display()))
+    }
+
+    /// Write the layout as RON, creating the parent directories if needed
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+
+        let config = PrettyConfig::new().extensions(Extensions::IMPLICIT_SOME);
+        let content = ron::ser::to_string_pretty(self, config)?;
+        fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Number of windows in the layout
+    pub fn windows(&self) -> usize {
+        match self {
+            Layout::Container { children, .. } => children.iter().map(Layout::windows).sum(),
+            Layout::Window { .. } => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use speculoos::prelude::*;
+
+    use crate::ipc::Orientation;
+    use crate::layout::Layout;
+    use crate::matcher::WindowMatcher;
+
+    #[test]
+    fn should_parse_layout() {
+        let layout: Layout = ron::from_str(indoc! {r#"
+            Container(
+                orientation: Horizontal,
+                children: [
+                    Window(ratio: 0.6, matcher: (app_id: "^code$")),
+                    Container(
+                        orientation: Vertical,
+                        children: [
+                            Window(matcher: (app_id: "^foot$")),
+                            Window(matcher: (app_id: "^foot$", title: "logs")),
+                        ],
+                    ),
+                ],
+            )
+        "#})
+        .unwrap();
+
+        let Layout::Container {
+            orientation,
+            ratio,
+            children,
+        } = &layout
+        else {
+            panic!("root should be a container");
+        };
+
+        assert_that!(*orientation).is_equal_to(Orientation::Horizontal);
+        assert_that!(*ratio).is_none();
+        assert_that!(children[0]).is_equal_to(Layout::Window {
+            ratio: Some(0.6),
+            matcher: WindowMatcher::exact(Some("code"), None),
+        });
+        assert_that!(layout.windows()).is_equal_to(3);
+    }
+
+    #[test]
+    fn should_save_and_reload_layout() {
+        let dir = std::env::temp_dir().join(format!("wzm-layout-{}", std::process::id()));
+        let path = dir.join("layouts/dev.ron");
+        let layout = Layout::Container {
+            orientation: Orientation::Vertical,
+            ratio: None,
+            children: vec![
+                Layout::Window {
+                    ratio: Some(0.7),
+                    matcher: WindowMatcher::exact(Some("foot"), None),
+                },
+                Layout::Window {
+                    ratio: None,
+                    matcher: WindowMatcher::exact(None, Some("logs")),
+                },
+            ],
+        };
+
+        layout.save(&path).unwrap();
+        let saved = Layout::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_that!(saved).is_equal_to(layout);
+    }
+}
@@ -1,36 +1,291 @@
 use std::collections::HashSet;
-use std::fs;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-use serde::{Deserialize, Serialize};
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use smithay::input::keyboard::{ModifiersState, XkbConfig};
 use xkbcommon::xkb::Keysym;
 
-use crate::keybinding::{Action, KeyBinding, Modifier};
+use crate::input::InputConfig;
+use crate::keybinding::{
+    Action, BindingConflict, Key, KeyBinding, Mode, ModeConfig, Modifier, SequenceMatch,
+};
+use crate::loader::{some, ConfigLoader};
+use crate::window_rule::WindowRule;
 
 pub mod action;
+pub mod input;
+pub mod ipc;
 pub mod keybinding;
+pub mod layout;
+mod loader;
+pub mod matcher;
+pub mod window_rule;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WzmConfig {
-    pub gaps: u32,
+    pub gaps: Gaps,
+    #[serde(default)]
+    pub keyboard: Keyboard,
+    #[serde(default)]
+    pub cursor: CursorConfig,
+    /// Pointer device settings, see [`InputConfig`]
+    #[serde(default)]
+    pub input: Vec<InputConfig>,
     pub keybindings: Vec<KeyBinding>,
+    /// Settings for new windows matching their app_id or title, see [`WindowRule`]
+    #[serde(default)]
+    pub window_rules: Vec<WindowRule>,
+    /// Binding modes available in addition to `Normal` and `Resize`
+    #[serde(default)]
+    pub modes: Vec<ModeConfig>,
+    /// Modifier held to drag floating windows with the pointer
+    #[serde(default = "default_main_modifier")]
+    pub main_modifier: Modifier,
+    /// Milliseconds to wait for the next key of a sequence before cancelling it
+    #[serde(default = "default_sequence_timeout")]
+    pub sequence_timeout: u64,
+    /// Files this config was loaded from, including the included ones
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+    /// Bindings sharing their trigger with an earlier binding of the same file
+    #[serde(skip)]
+    pub conflicts: Vec<BindingConflict>,
+}
+
+/// Either `(inner: 10, outer: 4, smart: true)` or a single size for both gaps, e.g. `14`
+#[derive(Debug, Default, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct Gaps {
+    /// Space between two tiled windows
+    pub inner: u32,
+    /// Space between tiled windows and the workspace edges
+    pub outer: u32,
+    /// Disable gaps on workspaces with a single tiled window
+    pub smart: bool,
+}
+
+impl<'de> Deserialize<'de> for Gaps {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct DetailedGaps {
+            #[serde(default)]
+            inner: u32,
+            #[serde(default)]
+            outer: u32,
+            #[serde(default)]
+            smart: bool,
+        }
+
+        struct GapsVisitor;
+
+        impl<'de> Visitor<'de> for GapsVisitor {
+            type Value = Gaps;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a gap size or (inner, outer, smart) gaps")
+            }
+
+            fn visit_u64<E: de::Error>(self, size: u64) -> Result<Gaps, E> {
+                let size = u32::try_from(size)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(size), &self))?;
+
+                Ok(Gaps {
+                    inner: size,
+                    outer: size,
+                    smart: false,
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Gaps, A::Error> {
+                let gaps = DetailedGaps::deserialize(MapAccessDeserializer::new(map))?;
+
+                Ok(Gaps {
+                    inner: gaps.inner,
+                    outer: gaps.outer,
+                    smart: gaps.smart,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(GapsVisitor)
+    }
+}
+
+/// XKB keymap and key repeat settings
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Keyboard {
+    #[serde(default)]
+    pub rules: String,
+    #[serde(default)]
+    pub model: String,
+    /// Comma separated layouts, e.g. `"us,ru"`
+    #[serde(default)]
+    pub layout: String,
+    #[serde(default)]
+    pub variant: String,
+    /// Comma separated XKB options, e.g. `"ctrl:nocaps,grp:alt_shift_toggle"`
+    #[serde(default)]
+    pub options: String,
+    /// Milliseconds before a held key starts repeating
+    #[serde(default = "default_repeat_delay")]
+    pub repeat_delay: i32,
+    /// Repeated keys per second
+    #[serde(default = "default_repeat_rate")]
+    pub repeat_rate: i32,
+}
+
+impl Keyboard {
+    pub fn xkb_config(&self) -> XkbConfig<'_> {
+        XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout: &self.layout,
+            variant: &self.variant,
+            options: Some(self.options.clone()).filter(|options| !options.is_empty()),
+        }
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self {
+            rules: String::new(),
+            model: String::new(),
+            layout: String::new(),
+            variant: String::new(),
+            options: String::new(),
+            repeat_delay: default_repeat_delay(),
+            repeat_rate: default_repeat_rate(),
+        }
+    }
+}
+
+/// Cursor theme, `XCURSOR_THEME` and `XCURSOR_SIZE` are used for unset fields
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CursorConfig {
+    #[serde(default, deserialize_with = "some")]
+    pub theme: Option<String>,
+    #[serde(default, deserialize_with = "some")]
+    pub size: Option<u32>,
 }
 
 impl WzmConfig {
     pub fn get() -> anyhow::Result<WzmConfig> {
-        let file = dirs::home_dir()
-            .expect("$HOME should be set")
-            .join(".config/wazemmes/config.ron");
+        Self::load(&Self::path())
+    }
+
+    /// Read and parse the config at the given path along with its includes,
+    /// parse errors are reported with their line and column.
+    pub fn load(path: &Path) -> anyhow::Result<WzmConfig> {
+        let mut loader = ConfigLoader::default();
+        loader.load_file(path)?;
+        loader.finish()
+    }
+
+    /// Parse a config, includes are resolved from the current directory
+    pub fn parse(config: &str) -> anyhow::Result<WzmConfig> {
+        let mut loader = ConfigLoader::default();
+        loader.parse(config)?;
+        loader.finish()
+    }
+
+    /// The config location, either `$WZM_CONFIG`, `$XDG_CONFIG_HOME/wazemmes/config.ron`
+    /// or `$HOME/.config/wazemmes/config.ron`
+    pub fn path() -> PathBuf {
+        if let Some(path) = env::var_os("WZM_CONFIG").filter(|path| !path.is_empty()) {
+            return PathBuf::from(path);
+        }
+
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                dirs::home_dir()
+                    .expect("$HOME should be set")
+                    .join(".config")
+            })
+            .join("wazemmes/config.ron")
+    }
+
+    /// Whether the mode should be left after its first triggered binding
+    pub fn is_oneshot(&self, mode: &Mode) -> bool {
+        match mode {
+            Mode::Named(name) => self
+                .modes
+                .iter()
+                .any(|mode| &mode.name == name && mode.oneshot),
+            _ => false,
+        }
+    }
+
+    /// Whether the mode can be entered with this config
+    pub fn has_mode(&self, mode: &Mode) -> bool {
+        match mode {
+            Mode::Named(name) => self.modes.iter().any(|mode| &mode.name == name),
+            _ => true,
+        }
+    }
+
+    /// Match the keys pressed so far against the bindings of the mode.
+    /// A complete binding fires even if longer sequences start with the same keys,
+    /// the loader reports them as conflicts.
+    pub fn match_sequence(&self, keys: &[(ModifiersState, Key)], mode: &Mode) -> SequenceMatch {
+        let mut partial = false;
+        for binding in &self.keybindings {
+            match binding.match_sequence(keys, mode) {
+                SequenceMatch::Complete(action) => return SequenceMatch::Complete(action),
+                SequenceMatch::Partial => partial = true,
+                SequenceMatch::NoMatch => {}
+            }
+        }
+
+        if partial {
+            SequenceMatch::Partial
+        } else {
+            SequenceMatch::NoMatch
+        }
+    }
 
-        let file = fs::read_to_string(file)?;
-        let config = ron::from_str(&file)?;
-        Ok(config)
+    /// Action bound to the release of the key, if no other key was pressed since its press
+    pub fn match_release(
+        &self,
+        modifiers: ModifiersState,
+        key: &Key,
+        mode: &Mode,
+    ) -> Option<Action> {
+        self.keybindings
+            .iter()
+            .find_map(|binding| binding.match_release(modifiers, key, mode))
+    }
+
+    /// Fail if some keybindings are duplicated or shadowed by another one
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.conflicts.is_empty() {
+            return Ok(());
+        }
+
+        let conflicts: Vec<_> = self.conflicts.iter().map(ToString::to_string).collect();
+        anyhow::bail!("conflicting keybindings:\n  {}", conflicts.join("\n  "))
     }
 }
 
 impl Default for WzmConfig {
     fn default() -> Self {
         Self {
-            gaps: 14,
+            gaps: Gaps {
+                inner: 14,
+                outer: 14,
+                smart: false,
+            },
+            keyboard: Keyboard::default(),
+            cursor: CursorConfig::default(),
+            input: vec![],
             keybindings: vec![
                 KeyBinding {
                     modifiers: HashSet::from([Modifier::Alt]),
@@ -39,7 +294,10 @@ impl Default for WzmConfig {
                         env: vec![],
                         command: "alacritty".to_string(),
                     },
+                    code: None,
                     mode: Default::default(),
+                    then: vec![],
+                    release: false,
                 },
                 KeyBinding {
                     modifiers: HashSet::from([Modifier::Alt]),
@@ -48,9 +306,98 @@ impl Default for WzmConfig {
                         env: vec![("WGPU_BACKEND".into(), "vulkan".into())],
                         command: "onagre".to_string(),
                     },
+                    code: None,
                     mode: Default::default(),
+                    then: vec![],
+                    release: false,
                 },
             ],
+            window_rules: vec![],
+            modes: vec![],
+            main_modifier: default_main_modifier(),
+            sequence_timeout: default_sequence_timeout(),
+            sources: vec![],
+            conflicts: vec![],
         }
     }
 }
+
+pub(crate) fn default_sequence_timeout() -> u64 {
+    1000
+}
+
+pub(crate) fn default_main_modifier() -> Modifier {
+    Modifier::Alt
+}
+
+fn default_repeat_delay() -> i32 {
+    200
+}
+
+fn default_repeat_rate() -> i32 {
+    25
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use speculoos::prelude::*;
+
+    use crate::{Gaps, WzmConfig};
+
+    #[test]
+    fn should_parse_single_size_gaps() {
+        let config = WzmConfig::parse("(gaps: 14, keybindings: [])").unwrap();
+
+        assert_that!(config.gaps).is_equal_to(Gaps {
+            inner: 14,
+            outer: 14,
+            smart: false,
+        });
+    }
+
+    #[test]
+    fn should_parse_detailed_gaps() {
+        let config = WzmConfig::parse("(gaps: (inner: 10, smart: true))").unwrap();
+
+        assert_that!(config.gaps).is_equal_to(Gaps {
+            inner: 10,
+            outer: 0,
+            smart: true,
+        });
+    }
+
+    #[test]
+    fn should_report_parse_error_position() {
+        let config = indoc! {r#"
+            (
+                gaps: (inner: 14, outer: 14),
+                keybindings: [
+                    (modifiers: [Alt], key: "t", action: Unknown),
+                ]
+            )
+        "#};
+
+        let error = WzmConfig::parse(config).unwrap_err().to_string();
+
+        assert_that!(error).starts_with("4:");
+        assert_that!(error).contains("Unknown");
+    }
+
+    #[test]
+    fn should_report_invalid_keysym() {
+        let config = indoc! {r#"
+            (
+                gaps: (inner: 14, outer: 14),
+                keybindings: [
+                    (modifiers: [Alt], key: "not-a-key", action: CloseWindow),
+                ]
+            )
+        "#};
+
+        let error = WzmConfig::parse(config).unwrap_err().to_string();
+
+        assert_that!(error).starts_with("4:");
+        assert_that!(error).contains("not-a-key");
+    }
+}
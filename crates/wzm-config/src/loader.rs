@@ -0,0 +1,648 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use log::warn;
+use serde::{Deserialize, Deserializer};
+use xkbcommon::xkb;
+use xkbcommon::xkb::Keysym;
+
+use crate::input::InputConfig;
+use crate::keybinding::{Action, BindingConflict, KeyBinding, ModeConfig, Modifier};
+use crate::window_rule::WindowRule;
+use crate::{
+    default_main_modifier, default_sequence_timeout, CursorConfig, Gaps, Keyboard, WzmConfig,
+};
+
+/// A config file as written by the user, before its includes are resolved
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    include: Vec<PathBuf>,
+    #[serde(default, deserialize_with = "some")]
+    gaps: Option<Gaps>,
+    #[serde(default, deserialize_with = "some")]
+    keyboard: Option<Keyboard>,
+    #[serde(default, deserialize_with = "some")]
+    cursor: Option<CursorConfig>,
+    #[serde(default)]
+    input: Vec<InputConfig>,
+    #[serde(default, deserialize_with = "some")]
+    main_modifier: Option<Modifier>,
+    #[serde(default, deserialize_with = "some")]
+    sequence_timeout: Option<u64>,
+    #[serde(default)]
+    modes: Vec<ModeConfig>,
+    #[serde(default)]
+    keybindings: Vec<KeyBinding>,
+    #[serde(default)]
+    window_rules: Vec<WindowRule>,
+}
+
+/// Merge config files in a deterministic order: included files are applied first,
+/// in the order they are listed, then the including file on top of them.
+/// A later keybinding with the same keys, modifiers and mode replaces the earlier one,
+/// a later mode or input device with the same name replaces the earlier one,
+/// window rules are appended so that the including file rules apply last,
+/// and the last defined gaps, keyboard, cursor, main modifier and sequence timeout win.
+/// Within a single file, only the first binding for a trigger is kept and the others
+/// are reported as conflicts, since they could never fire. So are merged bindings whose
+/// keys start with a shorter binding, or on the same physical key as an earlier one.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigLoader {
+    gaps: Option<Gaps>,
+    keyboard: Option<Keyboard>,
+    cursor: Option<CursorConfig>,
+    input: Vec<InputConfig>,
+    main_modifier: Option<Modifier>,
+    sequence_timeout: Option<u64>,
+    keybindings: Vec<KeyBinding>,
+    window_rules: Vec<WindowRule>,
+    modes: Vec<ModeConfig>,
+    conflicts: Vec<BindingConflict>,
+    sources: Vec<PathBuf>,
+    stack: Vec<PathBuf>,
+}
+
+impl ConfigLoader {
+    pub(crate) fn load_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        if self.stack.contains(&canonical) {
+            bail!("include cycle on {}", path.display());
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        let file: ConfigFile = ron::from_str(&content)
+            .with_context(|| format!("invalid config {}", path.display()))?;
+
+        let dir = canonical
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        self.stack.push(canonical.clone());
+        self.sources.push(canonical);
+        self.apply(file, &dir)?;
+        self.stack.pop();
+        Ok(())
+    }
+
+    pub(crate) fn parse(&mut self, content: &str) -> anyhow::Result<()> {
+        let file: ConfigFile = ron::from_str(content)?;
+        self.apply(file, Path::new("."))
+    }
+
+    pub(crate) fn finish(mut self) -> anyhow::Result<WzmConfig> {
+        self.report_shadowed();
+        for conflict in &self.conflicts {
+            warn!("Key-Binding {conflict}");
+        }
+
+        let config = WzmConfig {
+            gaps: self.gaps.unwrap_or(WzmConfig::default().gaps),
+            keyboard: self.keyboard.unwrap_or_default(),
+            cursor: self.cursor.unwrap_or_default(),
+            input: self.input,
+            keybindings: self.keybindings,
+            window_rules: self.window_rules,
+            modes: self.modes,
+            main_modifier: self.main_modifier.unwrap_or_else(default_main_modifier),
+            sequence_timeout: self
+                .sequence_timeout
+                .unwrap_or_else(default_sequence_timeout),
+            sources: self.sources,
+            conflicts: self.conflicts,
+        };
+
+        for binding in &config.keybindings {
+            let steps = std::iter::once((binding.key, binding.code))
+                .chain(binding.then.iter().map(|press| (press.key, press.code)));
+            for (key, code) in steps {
+                if key == Keysym::NoSymbol && code.is_none() {
+                    bail!("keybinding to {:?} needs a key or a code", binding.action);
+                }
+            }
+
+            if binding.release && !binding.then.is_empty() {
+                bail!("release keybindings can't be key sequences");
+            }
+
+            let entered = match &binding.action {
+                Action::EnterMode(mode) => Some(mode),
+                _ => None,
+            };
+
+            for mode in std::iter::once(&binding.mode).chain(entered) {
+                if !config.has_mode(mode) {
+                    bail!("mode '{mode}' is used by a keybinding but not declared in `modes`");
+                }
+            }
+        }
+
+        for rule in &config.window_rules {
+            if rule
+                .opacity
+                .is_some_and(|opacity| !(0.0..=1.0).contains(&opacity))
+            {
+                bail!("window rule opacity must be between 0.0 and 1.0");
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Report the merged bindings that can never fire: those whose keys start with the keys
+    /// of a shorter binding, or on the same physical key as an earlier one
+    fn report_shadowed(&mut self) {
+        let keymap = self
+            .keybindings
+            .iter()
+            .any(|binding| binding.code.is_some() || binding.then.iter().any(|p| p.code.is_some()))
+            .then(|| keymap(&self.keyboard.clone().unwrap_or_default()))
+            .flatten();
+
+        let raw_syms = |code: u32| {
+            keymap
+                .as_ref()
+                .map(|keymap| keymap.key_get_syms_by_level(code.into(), 0, 0).to_vec())
+                .unwrap_or_default()
+        };
+
+        for (idx, shadowed) in self.keybindings.iter().enumerate() {
+            let first = self
+                .keybindings
+                .iter()
+                .enumerate()
+                .find(|(first_idx, first)| {
+                    *first_idx != idx
+                        && (*first_idx < idx || first.then.len() < shadowed.then.len())
+                        && first.shadows(shadowed, raw_syms)
+                });
+
+            if let Some((_, first)) = first {
+                self.conflicts
+                    .push(BindingConflict::new(first, shadowed.clone()));
+            }
+        }
+    }
+
+    fn apply(&mut self, file: ConfigFile, dir: &Path) -> anyhow::Result<()> {
+        for include in &file.include {
+            self.load_file(&dir.join(include))?;
+        }
+
+        if file.gaps.is_some() {
+            self.gaps = file.gaps;
+        }
+
+        if file.keyboard.is_some() {
+            self.keyboard = file.keyboard;
+        }
+
+        if file.cursor.is_some() {
+            self.cursor = file.cursor;
+        }
+
+        if file.main_modifier.is_some() {
+            self.main_modifier = file.main_modifier;
+        }
+
+        if file.sequence_timeout.is_some() {
+            self.sequence_timeout = file.sequence_timeout;
+        }
+
+        for mode in file.modes {
+            match self.modes.iter_mut().find(|m| m.name == mode.name) {
+                Some(existing) => *existing = mode,
+                None => self.modes.push(mode),
+            }
+        }
+
+        for input in file.input {
+            match self.input.iter_mut().find(|i| i.name == input.name) {
+                Some(existing) => *existing = input,
+                None => self.input.push(input),
+            }
+        }
+
+        self.window_rules.extend(file.window_rules);
+
+        let mut seen: Vec<KeyBinding> = vec![];
+        for binding in file.keybindings {
+            if let Some(first) = seen.iter().find(|first| first.same_trigger(&binding)) {
+                self.conflicts.push(BindingConflict::new(first, binding));
+                continue;
+            }
+
+            seen.push(binding.clone());
+            let existing = self
+                .keybindings
+                .iter_mut()
+                .find(|existing| existing.same_trigger(&binding));
+
+            match existing {
+                Some(existing) => *existing = binding,
+                None => self.keybindings.push(binding),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Keymap of the configured layouts, used to find the symbols of raw keycodes
+fn keymap(keyboard: &Keyboard) -> Option<xkb::Keymap> {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let options = Some(keyboard.options.clone()).filter(|options| !options.is_empty());
+    xkb::Keymap::new_from_names(
+        &context,
+        &keyboard.rules,
+        &keyboard.model,
+        &keyboard.layout,
+        &keyboard.variant,
+        options,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+}
+
+// Fields are written without `Some(..)` in config files
+pub(crate) fn some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use indoc::indoc;
+    use smithay::input::keyboard::Keysym;
+    use speculoos::prelude::*;
+
+    use crate::keybinding::{Action, Key, Mode, Modifier};
+    use crate::{Gaps, WzmConfig};
+
+    fn config_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wzm-config-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn should_merge_included_config() {
+        let dir = config_dir("include");
+
+        let base = indoc! {r#"
+            (
+                gaps: (inner: 4, outer: 4),
+                keybindings: [
+                    (modifiers: [Alt], key: "a", action: CloseWindow),
+                    (modifiers: [Alt], key: "t", action: Run(env: [], command: "foot")),
+                ]
+            )
+        "#};
+
+        let config = indoc! {r#"
+            (
+                include: ["base.ron"],
+                keybindings: [
+                    (modifiers: [Alt], key: "t", action: Run(env: [], command: "alacritty")),
+                    (modifiers: [Alt], key: "q", action: Quit),
+                ]
+            )
+        "#};
+
+        fs::write(dir.join("base.ron"), base).unwrap();
+        fs::write(dir.join("config.ron"), config).unwrap();
+
+        let config = WzmConfig::load(&dir.join("config.ron")).unwrap();
+
+        assert_that!(config.gaps).is_equal_to(Gaps {
+            inner: 4,
+            outer: 4,
+            smart: false,
+        });
+
+        let actions: Vec<_> = config.keybindings.iter().map(|b| &b.action).collect();
+        assert_that!(actions).is_equal_to(vec![
+            &Action::CloseWindow,
+            &Action::Run {
+                env: vec![],
+                command: "alacritty".to_string(),
+            },
+            &Action::Quit,
+        ]);
+        assert_that!(config.sources).has_length(2);
+        assert_that!(config.conflicts).is_empty();
+    }
+
+    #[test]
+    fn should_report_duplicate_and_shadowed_bindings() {
+        let config = indoc! {r#"
+            (
+                keybindings: [
+                    (modifiers: [Alt], key: "k", action: MoveFocusUp),
+                    (modifiers: [Alt], key: "q", action: Quit),
+                    (modifiers: [Alt], key: "k", action: MoveFocusUp),
+                    (modifiers: [Alt], key: "q", action: CloseWindow),
+                    (modifiers: [Alt], key: "q", action: Quit, mode: Resize),
+                ]
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.keybindings).has_length(3);
+        assert_that!(config.keybindings[1].action).is_equal_to(Action::Quit);
+        assert_that!(config.conflicts).has_length(2);
+
+        let duplicate = &config.conflicts[0];
+        assert_that!(duplicate.is_duplicate()).is_true();
+        assert_that!(duplicate.to_string())
+            .is_equal_to("Alt+k in Normal mode is bound twice to MoveFocusUp".to_string());
+
+        let shadowed = &config.conflicts[1];
+        assert_that!(shadowed.is_duplicate()).is_false();
+        assert_that!(shadowed.first).is_equal_to(Action::Quit);
+        assert_that!(shadowed.shadowed).is_equal_to(Action::CloseWindow);
+    }
+
+    #[test]
+    fn should_fail_validation_on_conflicts() {
+        let config = indoc! {r#"
+            (
+                keybindings: [
+                    (modifiers: [Shift, Alt], key: "q", action: Quit),
+                    (modifiers: [Alt, Shift], key: "q", action: CloseWindow),
+                ]
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+        let error = config.validate().unwrap_err().to_string();
+
+        assert_that!(error)
+            .contains("Alt+Shift+q in Normal mode is bound to Quit, shadowing CloseWindow");
+        assert_that!(WzmConfig::default().validate()).is_ok();
+    }
+
+    #[test]
+    fn should_reject_include_cycle() {
+        let dir = config_dir("cycle");
+
+        fs::write(dir.join("a.ron"), r#"(include: ["b.ron"])"#).unwrap();
+        fs::write(dir.join("b.ron"), r#"(include: ["a.ron"])"#).unwrap();
+
+        let error = WzmConfig::load(&dir.join("a.ron")).unwrap_err();
+
+        assert_that!(format!("{error:#}")).contains("include cycle");
+    }
+
+    #[test]
+    fn should_parse_named_modes() {
+        let config = indoc! {r#"
+            (
+                modes: [(name: "launch", oneshot: true), (name: "system")],
+                keybindings: [
+                    (modifiers: [Alt], key: "o", action: EnterMode(Named("launch"))),
+                    (modifiers: [], key: "f", action: Run(env: [], command: "firefox"), mode: Named("launch")),
+                    (modifiers: [], key: "Escape", action: ExitMode, mode: Named("system")),
+                ]
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+        let launch = Mode::Named("launch".to_string());
+
+        assert_that!(config.keybindings[0].action).is_equal_to(Action::EnterMode(launch.clone()));
+        assert_that!(config.keybindings[1].mode).is_equal_to(launch.clone());
+        assert_that!(config.is_oneshot(&launch)).is_true();
+        assert_that!(config.is_oneshot(&Mode::Named("system".to_string()))).is_false();
+        assert_that!(config.has_mode(&Mode::Named("unknown".to_string()))).is_false();
+    }
+
+    #[test]
+    fn should_parse_key_sequences() {
+        let config = indoc! {r#"
+            (
+                sequence_timeout: 500,
+                keybindings: [
+                    (modifiers: [Logo], key: "w", then: [(key: "h")], action: MoveFocusLeft),
+                    (modifiers: [Logo], key: "w", then: [(key: "l")], action: MoveFocusRight),
+                ]
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.sequence_timeout).is_equal_to(500);
+        assert_that!(config.keybindings).has_length(2);
+        assert_that!(config.conflicts).is_empty();
+    }
+
+    #[test]
+    fn should_report_sequences_shadowed_by_a_prefix() {
+        let config = indoc! {r#"
+            (
+                keybindings: [
+                    (modifiers: [Logo], key: "w", then: [(key: "h")], action: MoveFocusLeft),
+                    (modifiers: [Logo], key: "w", action: CloseWindow),
+                    (modifiers: [Logo], key: "w", then: [(key: "l")], action: MoveFocusRight, mode: Resize),
+                ]
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.conflicts).has_length(1);
+        let conflict = &config.conflicts[0];
+        assert_that!(conflict.first).is_equal_to(Action::CloseWindow);
+        assert_that!(conflict.shadowed).is_equal_to(Action::MoveFocusLeft);
+        assert_that!(conflict.to_string()).is_equal_to(
+            "Logo+w h in Normal mode is bound to CloseWindow, shadowing MoveFocusLeft".to_string(),
+        );
+    }
+
+    #[test]
+    fn should_report_code_and_key_bindings_on_the_same_key() {
+        let config = indoc! {r#"
+            (
+                keyboard: (layout: "us"),
+                keybindings: [
+                    (modifiers: [Alt], code: 24, action: Quit),
+                    (modifiers: [Alt], key: "q", action: CloseWindow),
+                    (modifiers: [Alt], key: "w", action: ToggleFullScreenWindow),
+                ]
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.conflicts).has_length(1);
+        let conflict = &config.conflicts[0];
+        assert_that!(conflict.first).is_equal_to(Action::Quit);
+        assert_that!(conflict.shadowed).is_equal_to(Action::CloseWindow);
+    }
+
+    #[test]
+    fn should_parse_release_bindings() {
+        let config = indoc! {r#"
+            (
+                main_modifier: Logo,
+                keybindings: [
+                    (modifiers: [], key: "Super_L", release: true, action: Run(env: [], command: "onagre")),
+                    (modifiers: [Logo], key: "Super_L", action: CloseWindow),
+                ]
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.main_modifier).is_equal_to(Modifier::Logo);
+        assert_that!(config.conflicts).is_empty();
+        let key = Key {
+            sym: Keysym::Super_L,
+            raw_syms: vec![Keysym::Super_L],
+            code: 133,
+        };
+
+        assert_that!(config.match_release(Default::default(), &key, &Mode::Normal)).is_some();
+    }
+
+    #[test]
+    fn should_reject_release_sequence() {
+        let config = indoc! {r#"
+            (
+                keybindings: [
+                    (modifiers: [Logo], key: "w", then: [(key: "h")], release: true, action: CloseWindow),
+                ]
+            )
+        "#};
+
+        let error = WzmConfig::parse(config).unwrap_err().to_string();
+
+        assert_that!(error).contains("release");
+    }
+
+    #[test]
+    fn should_require_key_or_code() {
+        let config = indoc! {r#"
+            (
+                keybindings: [
+                    (modifiers: [Alt], code: 24, action: CloseWindow),
+                    (modifiers: [Alt], action: Quit),
+                ]
+            )
+        "#};
+
+        let error = WzmConfig::parse(config).unwrap_err().to_string();
+
+        assert_that!(error).contains("needs a key or a code");
+    }
+
+    #[test]
+    fn should_parse_keyboard() {
+        let config = indoc! {r#"
+            (
+                keyboard: (layout: "us,ru", options: "ctrl:nocaps,grp:alt_shift_toggle", repeat_rate: 40),
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.keyboard.layout.as_str()).is_equal_to("us,ru");
+        assert_that!(config.keyboard.repeat_rate).is_equal_to(40);
+        assert_that!(config.keyboard.repeat_delay).is_equal_to(200);
+        assert_that!(config.keyboard.xkb_config().options)
+            .is_equal_to(Some("ctrl:nocaps,grp:alt_shift_toggle".to_string()));
+    }
+
+    #[test]
+    fn should_parse_cursor() {
+        let config = indoc! {r#"
+            (
+                cursor: (theme: "Adwaita"),
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.cursor.theme).is_equal_to(Some("Adwaita".to_string()));
+        assert_that!(config.cursor.size).is_none();
+    }
+
+    #[test]
+    fn should_parse_window_rules() {
+        let config = indoc! {r#"
+            (
+                window_rules: [
+                    (matcher: (app_id: "^pavucontrol$"), floating: true, size: (800, 600)),
+                    (matcher: (title: "Picture-in-Picture"), opacity: 0.8),
+                ],
+            )
+        "#};
+
+        let config = WzmConfig::parse(config).unwrap();
+
+        assert_that!(config.window_rules).has_length(2);
+        assert_that!(config.window_rules[0].size).is_equal_to(Some((800, 600)));
+        assert_that!(config.window_rules[1].opacity).is_equal_to(Some(0.8));
+    }
+
+    #[test]
+    fn should_reject_invalid_opacity() {
+        let config = indoc! {r#"
+            (
+                window_rules: [(matcher: (app_id: "foot"), opacity: 1.5)],
+            )
+        "#};
+
+        let error = WzmConfig::parse(config).unwrap_err().to_string();
+
+        assert_that!(error).contains("opacity");
+    }
+
+    #[test]
+    fn should_report_misspelled_keys() {
+        let error = WzmConfig::parse("(keybinding: [])")
+            .unwrap_err()
+            .to_string();
+
+        assert_that!(error).contains("unknown field `keybinding`");
+
+        let config = indoc! {r#"
+            (
+                window_rules: [(matcher: (app_id: "foot"), floatin: true)],
+            )
+        "#};
+
+        let error = WzmConfig::parse(config).unwrap_err().to_string();
+
+        assert_that!(error).starts_with("2:");
+        assert_that!(error).contains("unknown field `floatin`");
+    }
+
+    #[test]
+    fn should_reject_undeclared_mode() {
+        let config = indoc! {r#"
+            (
+                keybindings: [
+                    (modifiers: [Alt], key: "o", action: EnterMode(Named("launch"))),
+                ]
+            )
+        "#};
+
+        let error = WzmConfig::parse(config).unwrap_err().to_string();
+
+        assert_that!(error).contains("mode 'launch'");
+    }
+}
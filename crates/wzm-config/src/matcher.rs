@@ -0,0 +1,136 @@
+use std::fmt;
+
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::loader::some;
+
+/// Select toplevels on their app_id and title, both are regexes and an unset field
+/// matches any window, e.g. `(app_id: "^firefox$", title: "Private")`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowMatcher {
+    #[serde(default, deserialize_with = "some")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<Pattern>,
+    #[serde(default, deserialize_with = "some")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<Pattern>,
+}
+
+/// A regex written as a plain string in config files
+#[derive(Clone)]
+pub struct Pattern(Regex);
+
+impl WindowMatcher {
+    /// Match windows having exactly this app_id, or this title when they have no app_id
+    pub fn exact(app_id: Option<&str>, title: Option<&str>) -> Self {
+        match app_id {
+            Some(app_id) => WindowMatcher {
+                app_id: Some(Pattern::exact(app_id)),
+                title: None,
+            },
+            None => WindowMatcher {
+                app_id: None,
+                title: title.map(Pattern::exact),
+            },
+        }
+    }
+
+    /// A window lacking a matched field, e.g. before it sets its app_id, never matches
+    pub fn matches(&self, app_id: Option<&str>, title: Option<&str>) -> bool {
+        let field = |pattern: &Option<Pattern>, value: Option<&str>| match pattern {
+            None => true,
+            Some(pattern) => value.is_some_and(|value| pattern.is_match(value)),
+        };
+
+        field(&self.app_id, app_id) && field(&self.title, title)
+    }
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Pattern)
+    }
+
+    /// Match the whole value literally
+    pub fn exact(value: &str) -> Self {
+        Pattern(Regex::new(&format!("^{}$", regex::escape(value))).expect("escaped regex"))
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Pattern::new(&pattern).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use speculoos::prelude::*;
+
+    use crate::matcher::WindowMatcher;
+
+    #[test]
+    fn should_match_app_id_and_title() {
+        let matcher: WindowMatcher =
+            ron::from_str(r#"(app_id: "^firefox$", title: "Private")"#).unwrap();
+
+        assert_that!(matcher.matches(Some("firefox"), Some("Private Browsing"))).is_true();
+        assert_that!(matcher.matches(Some("firefox"), Some("Inbox"))).is_false();
+        assert_that!(matcher.matches(Some("firefox-esr"), Some("Private"))).is_false();
+        assert_that!(matcher.matches(None, Some("Private"))).is_false();
+    }
+
+    #[test]
+    fn should_match_any_window_without_pattern() {
+        let matcher = WindowMatcher::default();
+
+        assert_that!(matcher.matches(None, None)).is_true();
+        assert_that!(matcher.matches(Some("foot"), Some("~"))).is_true();
+    }
+
+    #[test]
+    fn should_match_exact_app_id_literally() {
+        let matcher = WindowMatcher::exact(Some("org.gnome.Nautilus"), Some("Home"));
+
+        assert_that!(matcher.matches(Some("org.gnome.Nautilus"), Some("Trash"))).is_true();
+        assert_that!(matcher.matches(Some("orgXgnomeXNautilus"), None)).is_false();
+        assert_that!(ron::to_string(&matcher).unwrap())
+            .is_equal_to(r#"(app_id:"^org\\.gnome\\.Nautilus$")"#.to_string());
+    }
+
+    #[test]
+    fn should_reject_invalid_regex() {
+        let matcher = ron::from_str::<WindowMatcher>(r#"(app_id: "(unclosed")"#);
+
+        assert_that!(matcher).is_err();
+    }
+}
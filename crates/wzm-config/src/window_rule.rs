@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::loader::some;
+use crate::matcher::WindowMatcher;
+
+/// Settings applied to new toplevels on their first commit, once their app_id is known.
+/// Every rule matching a window applies in order, later ones overriding the fields
+/// they set, unset fields keep the default behavior.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowRule {
+    #[serde(default)]
+    pub matcher: WindowMatcher,
+    /// Open the window on this workspace instead of the current one
+    #[serde(default, deserialize_with = "some")]
+    pub workspace: Option<u8>,
+    /// Float or tile the window, by default only dialogs and fixed size windows float
+    #[serde(default, deserialize_with = "some")]
+    pub floating: Option<bool>,
+    /// Initial width and height of the window when floating
+    #[serde(default, deserialize_with = "some")]
+    pub size: Option<(i32, i32)>,
+    /// Initial position of the window when floating, relative to the usable area of
+    /// the workspace, it is centered otherwise
+    #[serde(default, deserialize_with = "some")]
+    pub position: Option<(i32, i32)>,
+    /// Let the client draw its borders and title bar, server side decorations
+    /// are requested by default so that it draws none
+    #[serde(default, deserialize_with = "some")]
+    pub borders: Option<bool>,
+    /// Between 0.0 and 1.0
+    #[serde(default, deserialize_with = "some")]
+    pub opacity: Option<f32>,
+    #[serde(default, deserialize_with = "some")]
+    pub fullscreen: Option<bool>,
+}
+
+impl WindowRule {
+    /// Overwrite the settings defined in `other`
+    pub fn merge(&mut self, other: &WindowRule) {
+        self.workspace = other.workspace.or(self.workspace);
+        self.floating = other.floating.or(self.floating);
+        self.size = other.size.or(self.size);
+        self.position = other.position.or(self.position);
+        self.borders = other.borders.or(self.borders);
+        self.opacity = other.opacity.or(self.opacity);
+        self.fullscreen = other.fullscreen.or(self.fullscreen);
+    }
+}
+
+/// Settings for a window, merged from every matching rule
+pub fn window_rule(rules: &[WindowRule], app_id: Option<&str>, title: Option<&str>) -> WindowRule {
+    let mut merged = WindowRule::default();
+    for rule in rules {
+        if rule.matcher.matches(app_id, title) {
+            merged.merge(rule);
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use speculoos::prelude::*;
+
+    use crate::window_rule::{window_rule, WindowRule};
+
+    fn rules() -> Vec<WindowRule> {
+        ron::from_str(indoc! {r#"
+            [
+                (matcher: (app_id: "^firefox$"), workspace: 2, opacity: 0.9),
+                (matcher: (app_id: "^firefox$", title: "^Picture-in-Picture$"),
+                    floating: true, size: (480, 270), position: (1400, 760), borders: false),
+                (matcher: (title: "(?i)video"), fullscreen: true),
+            ]
+        "#})
+        .unwrap()
+    }
+
+    #[test]
+    fn should_merge_matching_rules_in_order() {
+        let rule = window_rule(&rules(), Some("firefox"), Some("Picture-in-Picture"));
+
+        assert_that!(rule.workspace).is_equal_to(Some(2));
+        assert_that!(rule.opacity).is_equal_to(Some(0.9));
+        assert_that!(rule.floating).is_equal_to(Some(true));
+        assert_that!(rule.size).is_equal_to(Some((480, 270)));
+        assert_that!(rule.position).is_equal_to(Some((1400, 760)));
+        assert_that!(rule.borders).is_equal_to(Some(false));
+        assert_that!(rule.fullscreen).is_none();
+    }
+
+    #[test]
+    fn should_match_title_regex() {
+        let rule = window_rule(&rules(), Some("mpv"), Some("Funny VIDEO.mkv"));
+
+        assert_that!(rule.fullscreen).is_equal_to(Some(true));
+        assert_that!(rule.workspace).is_none();
+    }
+
+    #[test]
+    fn should_not_match_window_without_app_id() {
+        let rule = window_rule(&rules(), None, Some("firefox"));
+
+        assert_that!(rule).is_equal_to(WindowRule::default());
+    }
+
+    #[test]
+    fn should_override_earlier_rules() {
+        let rules: Vec<WindowRule> = ron::from_str(indoc! {r#"
+            [
+                (matcher: (app_id: "foot"), floating: true, workspace: 3),
+                (matcher: (app_id: "^foot$"), floating: false),
+            ]
+        "#})
+        .unwrap();
+
+        let rule = window_rule(&rules, Some("foot"), None);
+
+        assert_that!(rule.floating).is_equal_to(Some(false));
+        assert_that!(rule.workspace).is_equal_to(Some(3));
+    }
+}
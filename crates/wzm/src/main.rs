@@ -1,11 +1,18 @@
+use std::path::{Path, PathBuf};
+
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use wzm_comp::backend::udev::Udev;
 use wzm_comp::backend::winit::Winit;
 use wzm_comp::backend::Backend;
 use wzm_comp::{Display, EventLoop, State, Wzm};
 use wzm_config::WzmConfig;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = check_config_arg() {
+        check_config(&path);
+    }
+
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "wzm=debug,wzm_comp=debug".into()),
@@ -17,13 +24,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let loop_signal = event_loop.get_signal();
     let event_loop_handle = event_loop.handle();
     let display: Display<Wzm> = Display::new()?;
-    let winit = Winit::new(event_loop_handle.clone(), display.handle()).unwrap();
-    let state = State::new(event_loop_handle, display, winit.output());
+
+    let backend = match backend_kind() {
+        BackendKind::Winit => {
+            Backend::Winit(Winit::new(event_loop_handle.clone(), display.handle())?)
+        }
+        BackendKind::Udev => Backend::Udev(Udev::new(event_loop_handle.clone(), display.handle())?),
+    };
+
+    let config_path = config_arg().unwrap_or_else(WzmConfig::path);
+    let state = State::new(event_loop_handle, display, backend.seat_name(), config_path);
 
     let mut data = Wzm {
         state,
-        config: WzmConfig::get().unwrap(),
-        backend: Backend::Winit(winit),
+        backend,
         loop_signal,
     };
 
@@ -32,24 +46,89 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     event_loop
         .run(None, &mut data, |state| {
-            let ws = state.state.get_current_workspace();
-            let mut ws = ws.borrow_mut();
-            for (window, geometry, activate) in ws.get_pending_updates() {
-                if let Some(toplevel) = window.toplevel() {
-                    toplevel.with_pending_state(|state| {
-                        state.size = Some(geometry.size);
-                    });
-
-                    toplevel.send_configure();
-                }
-
-                state
-                    .state
-                    .space
-                    .map_element(window, geometry.loc, activate);
-            }
+            state.state.apply_pending_updates();
+
+            // Winit renders on redraw requests and udev on VBlanks, configures and other
+            // events sent while handling this dispatch must not wait for the next frame
+            let _ = state.state.display_handle.flush_clients();
         })
         .unwrap();
 
     Ok(())
 }
+
+/// Path given with `--config <path>`, if any
+fn config_arg() -> Option<PathBuf> {
+    std::env::args()
+        .skip_while(|arg| arg != "--config")
+        .nth(1)
+        .map(PathBuf::from)
+}
+
+/// Path given with `--check-config <path>`, if any
+fn check_config_arg() -> Option<PathBuf> {
+    std::env::args()
+        .skip_while(|arg| arg != "--check-config")
+        .nth(1)
+        .map(PathBuf::from)
+}
+
+/// Validate the config file and exit, with a non-zero code if it is invalid.
+/// Conflicting keybindings are only reported unless `--strict` is given.
+fn check_config(path: &Path) -> ! {
+    let strict = std::env::args().any(|arg| arg == "--strict");
+    let config = WzmConfig::load(path).and_then(|config| {
+        if strict {
+            config.validate()?;
+        }
+
+        Ok(config)
+    });
+
+    match config {
+        Ok(config) => {
+            for conflict in &config.conflicts {
+                eprintln!("warning: {conflict}");
+            }
+
+            println!("{}: ok", path.display());
+            std::process::exit(0);
+        }
+        Err(err) => {
+            eprintln!("{err:#}");
+            std::process::exit(1);
+        }
+    }
+}
+
+enum BackendKind {
+    Winit,
+    Udev,
+}
+
+/// Pick the backend from `--backend <winit|udev>` or `WZM_BACKEND`.
+/// Defaults to winit when started inside another graphical session, udev otherwise.
+fn backend_kind() -> BackendKind {
+    let mut args = std::env::args()
+        .skip_while(|arg| arg != "--backend")
+        .skip(1);
+    let requested = args.next().or_else(|| std::env::var("WZM_BACKEND").ok());
+
+    match requested.as_deref() {
+        Some("winit") => BackendKind::Winit,
+        Some("udev") | Some("tty") => BackendKind::Udev,
+        Some(other) => {
+            tracing::warn!("unknown backend '{other}', falling back to auto-detection");
+            detect_backend()
+        }
+        None => detect_backend(),
+    }
+}
+
+fn detect_backend() -> BackendKind {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_some() {
+        BackendKind::Winit
+    } else {
+        BackendKind::Udev
+    }
+}
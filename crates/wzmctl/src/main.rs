@@ -0,0 +1,273 @@
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use wzm_config::ipc::{
+    self, Event, EventKind, NodeInfo, Orientation, Request, Response, WindowInfo,
+};
+use wzm_config::keybinding::Action;
+
+const USAGE: &str = "\
+usage: wzmctl <command> [--json]
+
+commands:
+  action <action>  run an action, written as in the config, e.g. `MoveToWorkspace(2)`
+  reload           reload the config
+  workspaces       list the workspaces
+  focused          show the focused window
+  tree             show the layout of the current workspace
+  mode             show the current binding mode
+  subscribe [kind] print events as they happen, only the given kinds if any:
+                   window, workspace, mode, layout or config
+
+options:
+  --json           print the compositor response as JSON
+";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("wzmctl: {err:#}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> anyhow::Result<()> {
+    let json = env::args().any(|arg| arg == "--json");
+    let args: Vec<String> = env::args().skip(1).filter(|arg| arg != "--json").collect();
+
+    let request = match args.first().map(String::as_str) {
+        Some("action") => Request::Action(parse_action(&args[1..].join(" "))?),
+        Some("reload") => Request::Action(Action::Reload),
+        Some("workspaces") => Request::GetWorkspaces,
+        Some("focused") => Request::GetFocusedWindow,
+        Some("tree") => Request::GetTree,
+        Some("mode") => Request::GetMode,
+        Some("subscribe") => {
+            let kinds = args[1..]
+                .iter()
+                .map(|kind| parse_event_kind(kind))
+                .collect::<anyhow::Result<_>>()?;
+            return subscribe(kinds, json);
+        }
+        Some("-h" | "--help") => {
+            print!("{USAGE}");
+            return Ok(());
+        }
+        Some(other) => bail!("unknown command '{other}'\n\n{USAGE}"),
+        None => bail!("missing command\n\n{USAGE}"),
+    };
+
+    let response = send(&request)?;
+    if let Response::Error(err) = &response {
+        bail!("{err}");
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+        return Ok(());
+    }
+
+    match response {
+        Response::Ok | Response::Error(_) => {}
+        Response::Workspaces(workspaces) => {
+            for ws in workspaces {
+                let focused = if ws.focused { " (focused)" } else { "" };
+                println!("{}{focused}: {} windows", ws.num, ws.windows);
+            }
+        }
+        Response::FocusedWindow(window) => {
+            if let Some(window) = window {
+                println!("{}", describe_window(&window));
+            }
+        }
+        Response::Mode(mode) => println!("{mode}"),
+        Response::Tree(tree) => {
+            println!("workspace {}", tree.workspace);
+            print_node(&tree.root, 0);
+            if !tree.floating.is_empty() {
+                println!("floating");
+                for window in &tree.floating {
+                    println!("  {}", describe_window(window));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse an action written as in the config, e.g. `Run(env: [], command: "foot")`,
+/// or as JSON, e.g. `{"MoveToWorkspace": 2}`
+fn parse_action(action: &str) -> anyhow::Result<Action> {
+    let action = action.trim();
+    if action.is_empty() {
+        bail!("missing action\n\n{USAGE}");
+    }
+
+    if action.starts_with(['{', '"']) {
+        return serde_json::from_str(action)
+            .with_context(|| format!("invalid JSON action '{action}'"));
+    }
+
+    ron::from_str(action).with_context(|| format!("invalid action '{action}'"))
+}
+
+/// `$WZM_SOCK`, or the socket of the compositor running on `$WAYLAND_DISPLAY`
+fn socket_path() -> anyhow::Result<PathBuf> {
+    if let Some(path) = env::var_os(ipc::SOCKET_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let display = env::var_os("WAYLAND_DISPLAY")
+        .with_context(|| format!("neither {} nor WAYLAND_DISPLAY are set", ipc::SOCKET_ENV))?;
+    ipc::socket_path(&display).context("XDG_RUNTIME_DIR is not set")
+}
+
+fn parse_event_kind(kind: &str) -> anyhow::Result<EventKind> {
+    match kind {
+        "window" => Ok(EventKind::Window),
+        "workspace" => Ok(EventKind::Workspace),
+        "mode" => Ok(EventKind::Mode),
+        "layout" => Ok(EventKind::Layout),
+        "config" => Ok(EventKind::Config),
+        other => bail!("unknown event kind '{other}'"),
+    }
+}
+
+/// Send the request and return the reader for the response and the following events
+fn connect(request: &Request) -> anyhow::Result<BufReader<UnixStream>> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|| format!("failed to connect to {}", path.display()))?;
+
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    Ok(BufReader::new(stream))
+}
+
+fn read_response(reader: &mut BufReader<UnixStream>) -> anyhow::Result<Response> {
+    let mut response = String::new();
+    reader.read_line(&mut response)?;
+    serde_json::from_str(&response).context("invalid response from the compositor")
+}
+
+fn send(request: &Request) -> anyhow::Result<Response> {
+    read_response(&mut connect(request)?)
+}
+
+/// Print events until the compositor exits
+fn subscribe(kinds: Vec<EventKind>, json: bool) -> anyhow::Result<()> {
+    let mut reader = connect(&Request::Subscribe(kinds))?;
+    if let Response::Error(err) = read_response(&mut reader)? {
+        bail!("{err}");
+    }
+
+    for line in reader.lines() {
+        let line = line?;
+        if json {
+            println!("{line}");
+            continue;
+        }
+
+        let event: Event = serde_json::from_str(&line).context("invalid event")?;
+        println!("{}", describe_event(&event));
+    }
+
+    Ok(())
+}
+
+fn describe_event(event: &Event) -> String {
+    match event {
+        Event::WindowOpened(window) => format!("window opened: {}", describe_window(window)),
+        Event::WindowClosed(window) => format!("window closed: {}", describe_window(window)),
+        Event::WindowFocused(Some(window)) => {
+            format!("window focused: {}", describe_window(window))
+        }
+        Event::WindowFocused(None) => "window focused: none".to_string(),
+        Event::WindowTitleChanged(window) => {
+            format!("window title changed: {}", describe_window(window))
+        }
+        Event::WorkspaceSwitched(num) => format!("workspace switched: {num}"),
+        Event::ModeChanged(mode) => format!("mode changed: {mode}"),
+        Event::LayoutChanged(orientation) => {
+            format!("layout changed: {}", orientation_name(*orientation))
+        }
+        Event::ConfigReloaded => "config reloaded".to_string(),
+    }
+}
+
+fn orientation_name(orientation: Orientation) -> &'static str {
+    match orientation {
+        Orientation::Vertical => "vertical",
+        Orientation::Horizontal => "horizontal",
+    }
+}
+
+fn print_node(node: &NodeInfo, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match node {
+        NodeInfo::Container {
+            orientation,
+            children,
+            ..
+        } => {
+            println!("{indent}{}", orientation_name(*orientation));
+            for child in children {
+                print_node(child, depth + 1);
+            }
+        }
+        NodeInfo::Window { window, .. } => println!("{indent}{}", describe_window(window)),
+    }
+}
+
+fn describe_window(window: &WindowInfo) -> String {
+    let geometry = window.geometry;
+    let mut description = format!(
+        "{} \"{}\" {}x{}+{}+{}",
+        window.app_id.as_deref().unwrap_or("-"),
+        window.title.as_deref().unwrap_or_default(),
+        geometry.width,
+        geometry.height,
+        geometry.x,
+        geometry.y,
+    );
+
+    for (flag, name) in [
+        (window.focused, "focused"),
+        (window.floating, "floating"),
+        (window.fullscreen, "fullscreen"),
+    ] {
+        if flag {
+            description.push_str(&format!(" ({name})"));
+        }
+    }
+
+    description
+}
+
+#[cfg(test)]
+mod test {
+    use wzm_config::keybinding::{Action, Mode};
+
+    use crate::parse_action;
+
+    #[test]
+    fn should_parse_ron_and_json_actions() {
+        assert_eq!(
+            parse_action("MoveFocusLeft").unwrap(),
+            Action::MoveFocusLeft
+        );
+        assert_eq!(
+            parse_action("EnterMode(Named(\"launch\"))").unwrap(),
+            Action::EnterMode(Mode::Named("launch".to_string()))
+        );
+        assert_eq!(
+            parse_action(r#"{"MoveToWorkspace": 2}"#).unwrap(),
+            Action::MoveToWorkspace(2)
+        );
+        assert!(parse_action("Teleport").is_err());
+    }
+}